@@ -1,30 +1,33 @@
 use std::{collections::HashMap, io};
 
 use serde::{Serialize, Deserialize};
-use serde_json::Value;
 
-use crate::parser::{parse_parameters_from_url, get_item_from_json};
+use crate::{multipart::UploadedFiles, parser::get_item_from_json, request::{HttpRequest, REQUEST_ATTRIBUTES}};
 
 #[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
 pub struct Argument {
     pub source: String,
     pub name:   String,
+    /// which value of a repeated header or query parameter to pass: `first`, `last`, `all` or a zero-based index,
+    /// `None` for the default of the source
+    pub select: Option<String>,
 }
 
 impl Argument {
     pub fn new(builder: &HashMap<String, String>) -> Option<Self>{
-        if builder.len()!= 2 || !builder.contains_key("source") || !builder.contains_key("name"){
+        let valid_keys = ["source", "name", "select"];
+        if !builder.contains_key("source") || !builder.contains_key("name") || builder.keys().any(|k| !valid_keys.contains(&k.as_str())){
             return None;
         }
         let source = builder.get("source").unwrap().to_string();
         let name= builder.get("name").unwrap().to_string();
-        Some(Argument { source, name })
+        let select = builder.get("select").map(|s| s.to_string());
+        Some(Argument { source, name, select })
     }
 
-    fn get_argument_from_map(&self, map: &HashMap<String, String>, name: &String) -> Result<String, io::Error>{
-        let key = &self.name;
-        match map.get(key){ 
-            Some(v) => Ok(v.to_string()),
+    fn get_argument_from_value(&self, value: Option<String>, name: &String) -> Result<String, io::Error>{
+        match value{
+            Some(v) => Ok(v),
             None => {
                 let err_msg = format!("Failed to get parameter [{}]", &name);
                 log::error!("{}", err_msg);
@@ -37,33 +40,24 @@ impl Argument {
         }
     }
 
-    pub fn get_argument_from_header(&self, request: &HashMap<String, String>, name: &String) -> Result<String, io::Error>{
-        let invalid_headers = [
-            String::from("Method"), 
-            String::from("Url"), 
-            String::from("Version"), 
-            String::from("Body"),
-            ];
-        if invalid_headers.contains(name){
-            let err_msg = format!("Invalid Header Name [{}]", name);
-            log::error!("{}", err_msg);
+    pub fn get_argument_from_header(&self, request: &HttpRequest, name: &String) -> Result<String, io::Error>{
+        let select = self.select.as_deref().unwrap_or("all");
+        self.get_argument_from_value(request.headers.select(name, select), name)
+    }
 
-            return Err(io::Error::new(
-                io::ErrorKind::InvalidInput,
-                err_msg,
-            ))
-        }
-        self.get_argument_from_map(request, name)
+    pub fn get_argument_from_trailer(&self, request: &HttpRequest, name: &String) -> Result<String, io::Error>{
+        let select = self.select.as_deref().unwrap_or("all");
+        self.get_argument_from_value(request.trailers.select(name, select), name)
     }
 
-    pub fn get_argument_from_query(&self, request: &HashMap<String, String>, name: &String) -> Result<String, io::Error>{
-        let url = request.get("Url").unwrap();
-        let params = parse_parameters_from_url(url);
-        self.get_argument_from_map(&params, name)
+    pub fn get_argument_from_query(&self, request: &HttpRequest, name: &String) -> Result<String, io::Error>{
+        // the first value of a repeated parameter is passed unless told otherwise
+        let select = self.select.as_deref().unwrap_or("first");
+        self.get_argument_from_value(request.query_select(name, select), name)
     }
 
-    pub fn get_argument_from_payload(&self, request: &HashMap<String, String>, name: &String) -> Result<String, io::Error>{
-        if request.get("Method").unwrap() == "GET" {
+    pub fn get_argument_from_payload(&self, request: &HttpRequest, name: &String) -> Result<String, io::Error>{
+        if request.method == "GET" {
             let err_msg = format!("Could not parse argument [{}] from GET request with no payload", name);
             log::warn!("{}", err_msg);
 
@@ -72,14 +66,19 @@ impl Argument {
                 err_msg,
             ))
         }
-        let payload = request.get("Body").unwrap();
         if name.is_empty() {
             let err_msg = "Pass entire payload as argument".to_string();
             log::warn!("{}", err_msg);
-            return Ok(payload.to_string());
+            return match request.body_str(){
+                Some(p) => Ok(p.to_string()),
+                None => Err(Self::invalid_payload(name)),
+            };
         }
-        let v: Value = serde_json::from_str(payload).unwrap();
-        
+        let v = match request.payload(){
+            Some(v) => v,
+            None => return Err(Self::invalid_payload(name)),
+        };
+
         let err_msg = format!("Get argument [{}] from payload", name);
         log::warn!("{}", err_msg);
         match get_item_from_json(&v, name.as_str()){
@@ -88,14 +87,18 @@ impl Argument {
         }
     }
 
-    pub fn get_argument_from_request(&self, request: &HashMap<String, String>, name: &String) -> Result<String, io::Error>{
-        let valid_request_sources = [
-            "method",
-            "peer-address",
-            "url",
-            "version",
-        ];
-        if !valid_request_sources.contains(&name.to_lowercase().as_str()){
+    fn invalid_payload(name: &String) -> io::Error{
+        let err_msg = format!("Could not parse argument [{}] from request with no valid payload", name);
+        log::warn!("{}", err_msg);
+
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            err_msg,
+        )
+    }
+
+    pub fn get_argument_from_request(&self, request: &HttpRequest, name: &String) -> Result<String, io::Error>{
+        if !REQUEST_ATTRIBUTES.contains(&name.to_lowercase().as_str()){
             let err_msg = format!("Invalid request parameter [{}]", name);
             log::error!("{}", err_msg);
 
@@ -104,16 +107,26 @@ impl Argument {
                 err_msg,
             ))
         }
-        self.get_argument_from_map(request, name)
+        self.get_argument_from_value(request.attribute(name), name)
+    }
+
+    /// The saved path of a file uploaded in the form field `name` of a `multipart/form-data` request
+    pub fn get_argument_from_file(&self, uploads: Option<&UploadedFiles>, name: &String) -> Result<String, io::Error>{
+        let path = uploads
+            .and_then(|u| u.get_path(name))
+            .map(|p| p.to_string_lossy().to_string());
+        self.get_argument_from_value(path, name)
     }
 
-    pub fn parse_from_request(&self, request: &HashMap<String, String>) -> Result<String, io::Error>{
+    pub fn parse_from_request(&self, request: &HttpRequest, uploads: Option<&UploadedFiles>) -> Result<String, io::Error>{
         match self.source.as_str() {
             "string" => Ok(self.name.clone()),
             "payload" => self.get_argument_from_payload(request, &self.name),
             "query" => self.get_argument_from_query(request, &self.name),
             "header" => self.get_argument_from_header(request, &self.name),
+            "trailer" => self.get_argument_from_trailer(request, &self.name),
             "request" => self.get_argument_from_request(request, &self.name),
+            "file" => self.get_argument_from_file(uploads, &self.name),
             _ => {
                 let err_msg = format!("Invalid request parameter [{}]", self.name);
                 log::error!("{}", err_msg);
@@ -130,25 +143,19 @@ impl Argument {
 
 #[test]
 pub fn test_parse_arg(){
-    let request: HashMap<String, String> = HashMap::from([
-        ("Url".to_string(), "/webhook-test-1".to_string()),
-        ("User-Agent".to_string(), "curl/7.77.0".to_string()),
-        ("Version".to_string(), "HTTP/1.1".to_string()),
-        ("Method".to_string(), "POST".to_string()),
-        ("Accept".to_string(), "*/*".to_string()),
-        ("Host".to_string(), "127.0.0.1:7878".to_string()),
-        ("Content-Type".to_string(), "application/json".to_string()),
-        ("Content-Length".to_string(), "45".to_string()),
-        ("Body".to_string(), "{\"data\":{\"data2\":[\"val1\", \"val2\"], \"data3\": \"val3\"},\"data_s\":\"s_d\"}".to_string()),
-        ("Peer-Address".to_string(), "127.0.0.1:56020".to_string()),
-    ]);
+    let mut request = HttpRequest::new("POST", "/webhook-test-1", "HTTP/1.1", crate::request::test_peer());
+    request.headers.append("Host", "127.0.0.1:7878");
+    request.headers.append("Content-Type", "application/json");
+    request.headers.append("X-Forwarded-For", "10.0.0.1");
+    request.headers.append("x-forwarded-for", "10.0.0.2");
+    request.body = Some(b"{\"data\":{\"data2\":[\"val1\", \"val2\"], \"data3\": \"val3\"},\"data_s\":\"s_d\"}".to_vec());
 
     let map = HashMap::from([
         ("source".to_string(), "payload".to_string()),
         ("name".to_string(), "".to_string()),
     ]);
     let arg = Argument::new(&map).unwrap();
-    let res = arg.parse_from_request(&request).unwrap();
+    let res = arg.parse_from_request(&request, None).unwrap();
     println!("{}, {}", res, res.len());
 
     let map = HashMap::from([
@@ -156,7 +163,7 @@ pub fn test_parse_arg(){
         ("name".to_string(), "Method".to_string()),
     ]);
     let arg = Argument::new(&map).unwrap();
-    let res = arg.parse_from_request(&request).unwrap();
+    let res = arg.parse_from_request(&request, None).unwrap();
     println!("{}, {}", res, res.len());
     
     let map = HashMap::from([
@@ -164,7 +171,7 @@ pub fn test_parse_arg(){
         ("name".to_string(), "data.data2.1".to_string()),
     ]);
     let arg = Argument::new(&map).unwrap();
-    let res = arg.parse_from_request(&request).unwrap();
+    let res = arg.parse_from_request(&request, None).unwrap();
     println!("{}, {}", res, res.len());
 
     let map = HashMap::from([
@@ -172,7 +179,7 @@ pub fn test_parse_arg(){
         ("name".to_string(), "data.data3".to_string()),
     ]);
     let arg = Argument::new(&map).unwrap();
-    let res = arg.parse_from_request(&request).unwrap();
+    let res = arg.parse_from_request(&request, None).unwrap();
     println!("{}, {}", res, res.len());
 
     let map = HashMap::from([
@@ -180,7 +187,7 @@ pub fn test_parse_arg(){
         ("name".to_string(), "data_s".to_string()),
     ]);
     let arg = Argument::new(&map).unwrap();
-    let res = arg.parse_from_request(&request).unwrap();
+    let res = arg.parse_from_request(&request, None).unwrap();
     println!("{}, {}", res, res.len());
 
     let map = HashMap::from([
@@ -188,7 +195,7 @@ pub fn test_parse_arg(){
         ("name".to_string(), "Host".to_string()),
     ]);
     let arg = Argument::new(&map).unwrap();
-    let res = arg.parse_from_request(&request).unwrap();
+    let res = arg.parse_from_request(&request, None).unwrap();
     println!("{}, {}", res, res.len());
 
     let map = HashMap::from([
@@ -196,6 +203,56 @@ pub fn test_parse_arg(){
         ("name".to_string(), "str_param".to_string()),
     ]);
     let arg = Argument::new(&map).unwrap();
-    let res = arg.parse_from_request(&request).unwrap();
+    let res = arg.parse_from_request(&request, None).unwrap();
     println!("{}, {}", res, res.len());
-}
\ No newline at end of file
+
+    let map = HashMap::from([
+        ("source".to_string(), "header".to_string()),
+        ("name".to_string(), "x-forwarded-for".to_string()),
+        ("select".to_string(), "last".to_string()),
+    ]);
+    let arg = Argument::new(&map).unwrap();
+    assert_eq!("10.0.0.2", arg.parse_from_request(&request, None).unwrap());
+
+    // trailer fields are not headers, nor the other way round
+    request.trailers.append("X-Checksum", "abc");
+    let map = HashMap::from([
+        ("source".to_string(), "trailer".to_string()),
+        ("name".to_string(), "X-Checksum".to_string()),
+    ]);
+    let arg = Argument::new(&map).unwrap();
+    assert_eq!("abc", arg.parse_from_request(&request, None).unwrap());
+    let map = HashMap::from([
+        ("source".to_string(), "header".to_string()),
+        ("name".to_string(), "X-Checksum".to_string()),
+    ]);
+    let arg = Argument::new(&map).unwrap();
+    assert!(arg.parse_from_request(&request, None).is_err());
+}
+
+#[test]
+fn test_parse_query_arg(){
+    let request = HttpRequest::new("GET", "/webhook-test-1?env=prod&env=staging&msg=hello+world%21", "HTTP/1.1", crate::request::test_peer());
+
+    let map = HashMap::from([
+        ("source".to_string(), "query".to_string()),
+        ("name".to_string(), "env".to_string()),
+    ]);
+    let arg = Argument::new(&map).unwrap();
+    assert_eq!("prod", arg.parse_from_request(&request, None).unwrap());
+
+    let map = HashMap::from([
+        ("source".to_string(), "query".to_string()),
+        ("name".to_string(), "env".to_string()),
+        ("select".to_string(), "all".to_string()),
+    ]);
+    let arg = Argument::new(&map).unwrap();
+    assert_eq!("prod, staging", arg.parse_from_request(&request, None).unwrap());
+
+    let map = HashMap::from([
+        ("source".to_string(), "query".to_string()),
+        ("name".to_string(), "msg".to_string()),
+    ]);
+    let arg = Argument::new(&map).unwrap();
+    assert_eq!("hello world!", arg.parse_from_request(&request, None).unwrap());
+}
@@ -1,9 +1,8 @@
-use std::{collections::HashMap, fs, io};
-use crate::{config::{Config, configs::CONFIGS}, command::is_valid_command, parser::parse_hook_id_from_url};
+use std::{fs, io};
+use crate::{config::{Config, configs::CONFIGS}, command::is_valid_command, request::HttpRequest};
 
-pub fn is_webhook_id_in_configs(http_request: &HashMap<String, String>) -> Result<(), io::Error>{
-    let url = http_request.get("Url").unwrap();
-    let requested_id = parse_hook_id_from_url(url);
+pub fn is_webhook_id_in_configs(http_request: &HttpRequest) -> Result<(), io::Error>{
+    let requested_id = http_request.hook_id();
     let required_ids:Vec<String> = CONFIGS.get_webhook_ids()
     .iter()
     .map(|id|id.trim_start_matches('/').to_string())
@@ -23,6 +22,20 @@ pub fn is_webhook_id_in_configs(http_request: &HashMap<String, String>) -> Resul
     Ok(())
 }
 
+pub fn is_webhook_served_on_listener(config: &Config, listener: &str) -> Result<(), io::Error>{
+    if !config.is_served_on(listener) {
+        let err_msg = format!("request ignored with hook id {} not served on listener {}", config.id, listener);
+        log::warn!("{}", err_msg);
+
+        let error = io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "Undefined Hook Id",
+        );
+        return Err(error);
+    }
+    Ok(())
+}
+
 pub fn check_execute_command(config: &Config) -> io::Result<()>{
     let execute_command = &config.execute_command;
     let command_working_directory = &config.command_working_directory;
@@ -61,9 +74,18 @@ pub fn check_log_config(config: &Config) -> io::Result<()>{
     }
 }
 
-pub fn check_trigger_rules(config: &Config, http_request: &HashMap<String, String>) -> io::Result<()>{
+pub fn check_trigger_rules(config: &Config, http_request: &HttpRequest) -> io::Result<()>{
     // check trigger rules
     if let Some(r) = config.get_trigger_rule(){
+        // the HMAC rules read `body`, which is swapped for the decoded body if the hook asks so
+        let decoded_request;
+        let http_request = match &http_request.decoded_body {
+            Some(decoded) if config.hmac_over_decoded_body => {
+                decoded_request = HttpRequest { body: Some(decoded.clone()), ..http_request.clone() };
+                &decoded_request
+            },
+            _ => http_request,
+        };
         if !r.is_matched(http_request){
             let err_msg = format!("Failed to Trigger Hook [{}]: Rule Mismatch!", config.id);
             log::error!("{}", err_msg);
@@ -79,7 +101,7 @@ pub fn check_trigger_rules(config: &Config, http_request: &HashMap<String, Strin
     Ok(())
 }
 
-pub fn preflight_check(config: &Config, http_request: &HashMap<String, String>) -> Result<(), io::Error>{
+pub fn preflight_check(config: &Config, http_request: &HttpRequest) -> Result<(), io::Error>{
     check_execute_command(config)?;
     check_log_config(config)?;
     check_trigger_rules(config, http_request)?;
@@ -92,8 +114,7 @@ fn test_isnot_webhook_id_in_configs(){
     let config_file = format!("{}/src/tests/config/hooks.test.yaml", env!("CARGO_MANIFEST_DIR"));
     use std::env;
     env::set_var("CONFIG_PATH", config_file);
-    let mut http_request: HashMap<String, String> = HashMap::new();
-    http_request.insert("Url".to_string(), "/webhook-test-3/".to_string());
+    let http_request = HttpRequest::new("GET", "/webhook-test-3/", "HTTP/1.1", crate::request::test_peer());
     let r = is_webhook_id_in_configs(&http_request);
     assert!(r.is_err());
 }
@@ -103,8 +124,7 @@ fn test_is_webhook_id_in_configs(){
     let config_file = format!("{}/src/tests/config/hooks.test.yaml", env!("CARGO_MANIFEST_DIR"));
     use std::env;
     env::set_var("CONFIG_PATH", config_file);
-    let mut http_request: HashMap<String, String> = HashMap::new();
-    http_request.insert("Url".to_string(), "/webhook-test-1/?a=1&b=2".to_string());
+    let http_request = HttpRequest::new("GET", "/webhook-test-1/?a=1&b=2", "HTTP/1.1", crate::request::test_peer());
     let r = is_webhook_id_in_configs(&http_request);
     assert!(r.is_ok())
 }
@@ -122,3 +142,34 @@ fn test_check_log_config(){
     assert!(r.is_ok());
 }
 
+
+#[test]
+fn test_is_webhook_served_on_listener(){
+    let mut config = Config::new();
+    assert!(is_webhook_served_on_listener(&config, "public").is_ok());
+    config.listeners = vec!["local".to_string()];
+    assert!(is_webhook_served_on_listener(&config, "local").is_ok());
+    assert!(is_webhook_served_on_listener(&config, "public").is_err());
+}
+
+#[test]
+fn test_check_trigger_rules_over_decoded_body(){
+    use crypto::{hmac::Hmac, mac::Mac, sha2::Sha256};
+    use itertools::Itertools;
+
+    let decoded = b"{\"a\":\"b\"}".to_vec();
+    let mut hasher = Hmac::new(Sha256::new(), b"secret");
+    hasher.input(&decoded);
+    let signature = hasher.result().code().iter().format_with("", |byte, f| f(&format_args!("{:02x}", byte))).to_string();
+
+    let mut http_request = HttpRequest::new("POST", "/hook", "HTTP/1.1", crate::request::test_peer());
+    http_request.headers.append("X-Signature", &format!("sha256={}", signature));
+    http_request.body = Some(b"compressed".to_vec());
+    http_request.decoded_body = Some(decoded);
+
+    let mut config = Config::new();
+    config.trigger_rules = Some(serde_yaml::from_str("{kind: hmac-sha256, value: secret, source: header, name: X-Signature}").unwrap());
+    assert!(check_trigger_rules(&config, &http_request).is_err());
+    config.hmac_over_decoded_body = true;
+    assert!(check_trigger_rules(&config, &http_request).is_ok());
+}
@@ -1,7 +1,47 @@
-use std::{process::{Command, Child, Stdio}, str, collections::HashMap, io::{self}, net::TcpStream};
+use std::{process::{Command, Child, Stdio}, str, collections::HashMap, env, io::{self, Write}, sync::Mutex, thread, time::{Duration, Instant}};
+use lazy_static::lazy_static;
 use log::{Record, Log};
 
-use crate::{config::Config, response::{http_response_with_child, http_response_with_err}, arguments::Argument, mylog::{create_log_file, set_hook_logger}};
+use crate::{config::Config, multipart::UploadedFiles, request::HttpRequest, response::{http_response_with_child, http_response_with_err}, arguments::Argument, mylog::{create_log_file, set_hook_logger}};
+
+/// A hook command spawned by the server which is still running
+struct RunningCommand {
+    hook_id: String,
+    command: String,
+}
+
+lazy_static! {
+    /// running hook commands by their process id
+    static ref RUNNING_COMMANDS: Mutex<HashMap<u32, RunningCommand>> = Mutex::new(HashMap::new());
+}
+
+/// Keep track of a spawned hook command until it exits,
+/// the files uploaded for it are removed afterwards
+pub fn track_command(hook_id: &str, command: &str, mut child: Child, uploads: Option<UploadedFiles>){
+    let pid = child.id();
+    RUNNING_COMMANDS.lock().unwrap().insert(pid, RunningCommand { hook_id: hook_id.to_string(), command: command.to_string() });
+    thread::spawn(move || {
+        if let Err(e) = child.wait(){
+            log::error!("Failed to wait for command in process id {}: {}", pid, e);
+        }
+        drop(uploads);
+        RUNNING_COMMANDS.lock().unwrap().remove(&pid);
+    });
+}
+
+/// Wait up to `grace_period` for the tracked hook commands to exit,
+/// and log the ones still running afterwards. Returns the number of them.
+pub fn wait_for_running_commands(grace_period: Duration) -> usize{
+    let deadline = Instant::now() + grace_period;
+    while !RUNNING_COMMANDS.lock().unwrap().is_empty() && Instant::now() < deadline{
+        thread::sleep(Duration::from_millis(100));
+    }
+    let commands = RUNNING_COMMANDS.lock().unwrap();
+    for (pid, c) in commands.iter(){
+        log::warn!("Command [{}] of hook [{}] is still running at exit in process id: {}", c.command, c.hook_id, pid);
+    }
+    commands.len()
+}
 
 pub fn execute_script(cmd: &str, cwd: &str, stdout_log: &str, arguments: &Vec<String>) -> io::Result<Child>{
     let stdout_file = create_log_file(stdout_log).unwrap();
@@ -48,16 +88,28 @@ pub fn is_valid_command(command: &str, work_dir: &str) -> std::io::Result<bool>{
     } 
 }
 
-pub fn trigger_hook(stream: &mut TcpStream, config: &Config, http_request: &HashMap<String, String>) {
+pub fn trigger_hook(stream: &mut impl Write, config: &Config, http_request: &HttpRequest) {
+    // uploaded files are saved only for the hooks passing them to the command
+    let uploads = if config.pass_arguments_to_command.iter().any(|arg| arg.get("source").map(|s| s.as_str()) == Some("file")){
+        match UploadedFiles::save(http_request, &env::temp_dir()){
+            Ok(u) => Some(u),
+            Err(e) => {
+                log::error!("Failed to save uploaded files for hook [{}]: {}", config.id, e);
+                http_response_with_err(stream, &e, http_request, Some(config));
+                return;
+            }
+        }
+    } else {
+        None
+    };
+
     // find the right config from config file for the incoming request
     let arguments: Vec<String> = config.pass_arguments_to_command
     .iter()
-    .map(|arg| match Argument::new(arg)
+    .map(|arg| Argument::new(arg)
                                         .unwrap()
-                                        .parse_from_request(http_request){
-                                            Ok(r) => r,
-                                            Err(_) => String::new()   
-                                        }
+                                        .parse_from_request(http_request, uploads.as_ref())
+                                        .unwrap_or_default()
     )
     .filter(| arg | !arg.is_empty())
     .collect();
@@ -67,7 +119,8 @@ pub fn trigger_hook(stream: &mut TcpStream, config: &Config, http_request: &Hash
         Ok(c) => {
             let msg = format!("Command [{}] issued under dir {} in process id: {}", &config.execute_command, &config.command_working_directory, c.id());
             log::info!("{}", msg);
-            http_response_with_child(stream, &c, http_request, config)
+            http_response_with_child(stream, &c, http_request, config);
+            track_command(&config.id, &config.execute_command, c, uploads);
         },
         Err(e) => {
             let msg = format!("Failed to execute command {}: {}", &config.execute_command, e);
@@ -80,7 +133,7 @@ pub fn trigger_hook(stream: &mut TcpStream, config: &Config, http_request: &Hash
 #[test]
 fn test_execute_script(){
     let args = vec!["-a".to_string(), "-l".to_string()];
-    let log = format!("{}/logs/test.log", env!("CARGO_MANIFEST_DIR"));
+    let log = format!("{}/rusthook-test-logs/test.log", std::env::temp_dir().display());
     let _ = execute_script("ls", "/", &log, &args);
 }
 
@@ -102,3 +155,10 @@ fn test_is_valid_command_test_sh(){
     let res = is_valid_command("./test.sh", &work_dir);
     assert!(res.unwrap())
 }
+
+#[test]
+fn test_wait_for_running_commands(){
+    let child = Command::new("sleep").arg("0.2").spawn().unwrap();
+    track_command("test-hook", "sleep", child, None);
+    assert_eq!(0, wait_for_running_commands(Duration::from_secs(5)));
+}
@@ -1,9 +1,9 @@
 use std::sync::Arc;
 use serde::{Serialize, Deserialize};
-use std::{fs, collections::HashMap};
+use std::fs;
 use std::env;
 
-use crate::parser::parse_hook_id_from_url;
+use crate::request::HttpRequest;
 
 use super::{Config, global::GlobalConfig};
 use lazy_static::lazy_static;
@@ -28,7 +28,33 @@ impl Configs {
                 panic!("Cannot read file: {}, {}", config_file, e);
             }
         };
-        serde_yaml::from_str(configs_str.as_str()).unwrap_or_else(|_| panic!("Cannot parse configs from config file: [{}]", config_file))
+        let configs: Configs = serde_yaml::from_str(configs_str.as_str()).unwrap_or_else(|_| panic!("Cannot parse configs from config file: [{}]", config_file));
+        if let Err(e) = configs.validate(){
+            panic!("Invalid configs in config file [{}]: {}", config_file, e);
+        }
+        configs
+    }
+
+    /// Check the trusted proxies and their header, the rate limits, and the methods, cors and trigger rules of every hook
+    pub fn validate(&self) -> Result<(), String>{
+        self.global.get_forwarded_header().map_err(|e| format!("forwarded_header: {}", e))?;
+        self.global.get_trusted_proxies().map_err(|e| format!("trusted_proxies: {}", e))?;
+        if let Some(limit) = &self.global.client_rate_limit{
+            limit.validate().map_err(|e| format!("client_rate_limit: {}", e))?;
+        }
+        for hook in self.hooks.iter(){
+            hook.validate_http_methods().map_err(|e| format!("hook [{}]: {}", hook.id, e))?;
+            if let Some(cors) = &hook.cors{
+                cors.validate().map_err(|e| format!("hook [{}]: {}", hook.id, e))?;
+            }
+            if let Some(limit) = &hook.rate_limit{
+                limit.validate().map_err(|e| format!("hook [{}]: {}", hook.id, e))?;
+            }
+            if let Some(rule) = hook.get_trigger_rule(){
+                rule.validate().map_err(|e| format!("hook [{}]: {}", hook.id, e))?;
+            }
+        }
+        Ok(())
     }
 
     pub fn get_webhook_ids(&self) -> Vec<String>{
@@ -39,11 +65,10 @@ impl Configs {
         webhook_ids
     }
 
-    pub fn get_config_by_http_request(&self, http_request: &HashMap<String, String>) -> Config{
+    pub fn get_config_by_http_request(&self, http_request: &HttpRequest) -> Config{
         let mut config: Config = Config::new();
         let mut is_default = true;
-        let url = http_request.get("Url").unwrap();
-        let requested_id = parse_hook_id_from_url(url);
+        let requested_id = http_request.hook_id();
         for item in self.hooks.iter(){
             if item.id == requested_id{
                 config = item.clone();
@@ -56,6 +81,15 @@ impl Configs {
         config
     }
 
+    /// The largest request body accepted by the hook, or by the server if no such hook is defined
+    pub fn get_max_body_size(&self, hook_id: &str) -> u64{
+        self.hooks
+            .iter()
+            .find(|h| h.id == hook_id)
+            .and_then(|h| h.max_body_size)
+            .unwrap_or(self.global.max_body_size)
+    }
+
     pub fn get_global_log_path(&self) -> String{
         self.global.get_log_path()
     }
@@ -84,13 +118,66 @@ fn test_get_webhook_ids(){
 fn test_get_config_by_http_request(){
     let config_file = format!("{}/src/tests/config/hooks.test.yaml", env!("CARGO_MANIFEST_DIR"));
     let configs = Configs::new(&config_file);
-    let mut http_request: HashMap<String, String> = HashMap::new();
-    http_request.insert("Url".to_string(), "/webhook-test-2/".to_string());
+    let http_request = HttpRequest::new("GET", "/webhook-test-2/", "HTTP/1.1", crate::request::test_peer());
     println!("{:#?}", configs.get_config_by_http_request(&http_request));
 }
 
+#[test]
+fn test_get_max_body_size(){
+    let config_file = format!("{}/src/tests/config/hooks.test.yaml", env!("CARGO_MANIFEST_DIR"));
+    let configs = Configs::new(&config_file);
+    assert_eq!(1024, configs.get_max_body_size("webhook-test-2"));
+    assert_eq!(GlobalConfig::default_max_body_size(), configs.get_max_body_size("webhook-test-1"));
+    assert_eq!(GlobalConfig::default_max_body_size(), configs.get_max_body_size("undefined"));
+}
+
 #[test]
 fn test_global_config(){
     let _config_file = format!("{}/src/tests/config/hooks.test.yaml", env!("CARGO_MANIFEST_DIR"));
 
-}
\ No newline at end of file
+}
+#[test]
+fn test_validate_configs(){
+    let configs: Configs = serde_yaml::from_str("
+global: {}
+hooks:
+- id: office-only
+  trigger_rules:
+    not:
+      kind: ip-whitelist
+      value: 10.0.0.0/8, 2001:db8::/33
+").unwrap();
+    assert!(configs.validate().is_ok());
+
+    let configs: Configs = serde_yaml::from_str("
+global: {}
+hooks:
+- id: office-only
+  trigger_rules:
+    and:
+    - kind: ip-whitelist
+      value: 10.0.0.0/8, office
+").unwrap();
+    assert!(configs.validate().unwrap_err().contains("office-only"));
+
+    let configs: Configs = serde_yaml::from_str("
+global:
+  client_rate_limit: {requests: 0}
+hooks: []
+").unwrap();
+    assert!(configs.validate().is_err());
+
+    let configs: Configs = serde_yaml::from_str("
+global:
+  trusted_proxies: [10.0.0.0/8, 10.0.0.300]
+hooks: []
+").unwrap();
+    assert!(configs.validate().unwrap_err().contains("10.0.0.300"));
+
+    let configs: Configs = serde_yaml::from_str("
+global:
+  forwarded_header: x-client-ip
+hooks: []
+").unwrap();
+    assert!(configs.validate().unwrap_err().contains("forwarded_header"));
+}
@@ -0,0 +1,100 @@
+use serde::{Serialize, Deserialize};
+
+/// Cross-origin requests from browsers allowed to a hook
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+pub struct CorsConfig {
+    /// origins like `https://dashboard.example.com`, or `*` for any origin without credentials
+    pub allowed_origins: Vec<String>,
+
+    /// the methods allowed by a preflight, the same as the hook's `http_methods` if not specified
+    #[serde(default = "CorsConfig::default_allowed_methods")]
+    pub allowed_methods: Vec<String>,
+
+    /// the request headers allowed by a preflight, or `*` for any header
+    #[serde(default = "CorsConfig::default_allowed_headers")]
+    pub allowed_headers: Vec<String>,
+
+    #[serde(default = "CorsConfig::default_allow_credentials")]
+    pub allow_credentials: bool,
+
+    /// how many seconds a browser can cache the result of a preflight
+    #[serde(default = "CorsConfig::default_max_age")]
+    pub max_age: Option<u64>,
+}
+
+impl CorsConfig {
+    pub fn new(allowed_origins: Vec<String>) -> Self{
+        CorsConfig {
+            allowed_origins,
+            allowed_methods: Self::default_allowed_methods(),
+            allowed_headers: Self::default_allowed_headers(),
+            allow_credentials: Self::default_allow_credentials(),
+            max_age: Self::default_max_age(),
+        }
+    }
+
+    pub fn default_allowed_methods() -> Vec<String>{
+        Vec::new()
+    }
+
+    pub fn default_allowed_headers() -> Vec<String>{
+        Vec::new()
+    }
+
+    pub fn default_allow_credentials() -> bool{
+        false
+    }
+
+    pub fn default_max_age() -> Option<u64>{
+        None
+    }
+
+    /// origins are compared case-insensitively, as browsers send them in lower case
+    pub fn allows_origin(&self, origin: &str) -> bool{
+        self.allowed_origins.iter().any(|o| o == "*" || o.eq_ignore_ascii_case(origin))
+    }
+
+    pub fn allows_header(&self, header: &str) -> bool{
+        self.allowed_headers.iter().any(|h| h == "*" || h.eq_ignore_ascii_case(header))
+    }
+
+    pub fn validate(&self) -> Result<(), String>{
+        if self.allowed_origins.is_empty() {
+            return Err("cors allowed_origins must not be empty".to_string());
+        }
+        // any page could make credentialed calls to the hook otherwise
+        if self.allow_credentials && self.allowed_origins.iter().any(|o| o == "*") {
+            return Err("cors allowed_origins must list the origins instead of `*` when allow_credentials is true".to_string());
+        }
+        // an origin is a scheme and host with an optional port, never a path
+        for origin in self.allowed_origins.iter().filter(|o| o.as_str() != "*"){
+            match origin.split_once("://") {
+                Some((scheme, host)) if !scheme.is_empty() && !host.is_empty() && !host.contains('/') => (),
+                _ => return Err(format!("Invalid cors origin [{}]", origin)),
+            }
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn test_cors_config(){
+    let mut cors = CorsConfig::new(vec!["https://dashboard.example.com".to_string()]);
+    assert!(cors.validate().is_ok());
+    assert!(cors.allows_origin("https://Dashboard.example.com"));
+    assert!(!cors.allows_origin("https://evil.example.com"));
+    assert!(!cors.allows_header("X-Token"));
+
+    cors.allowed_headers = vec!["x-token".to_string()];
+    assert!(cors.allows_header("X-Token"));
+
+    cors.allowed_origins = vec!["https://dashboard.example.com/".to_string()];
+    assert!(cors.validate().is_err());
+    cors.allowed_origins = Vec::new();
+    assert!(cors.validate().is_err());
+    cors.allowed_origins = vec!["*".to_string()];
+    assert!(cors.validate().is_ok());
+    assert!(cors.allows_origin("http://localhost:3000"));
+    cors.allow_credentials = true;
+    assert!(cors.validate().is_err());
+}
@@ -1,6 +1,12 @@
+use std::time::Duration;
+
 use log::LevelFilter;
 use serde::{Serialize, Deserialize};
 
+use crate::request::forwarded::{ForwardedHeader, TrustedProxies};
+
+use super::{listener::ListenerConfig, rate_limit::RateLimitConfig, tls::TlsConfig};
+
 
 #[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
 pub struct GlobalConfig {
@@ -15,6 +21,59 @@ pub struct GlobalConfig {
 
     #[serde(default = "GlobalConfig::default_rules_dir")]
     pub rules_dir: String,
+
+    #[serde(default = "GlobalConfig::default_keep_alive_timeout")]
+    pub keep_alive_timeout: u64,
+
+    #[serde(default = "GlobalConfig::default_max_requests_per_connection")]
+    pub max_requests_per_connection: usize,
+
+    #[serde(default = "GlobalConfig::default_read_timeout")]
+    pub read_timeout: u64,
+
+    /// overrides `read_timeout` for the header section of a request
+    #[serde(default = "GlobalConfig::default_header_timeout")]
+    pub header_timeout: Option<u64>,
+
+    /// overrides `read_timeout` for the body of a request
+    #[serde(default = "GlobalConfig::default_body_timeout")]
+    pub body_timeout: Option<u64>,
+
+    #[serde(default = "GlobalConfig::default_write_timeout")]
+    pub write_timeout: u64,
+
+    #[serde(default = "GlobalConfig::default_max_header_size")]
+    pub max_header_size: u64,
+
+    #[serde(default = "GlobalConfig::default_max_header_count")]
+    pub max_header_count: usize,
+
+    #[serde(default = "GlobalConfig::default_max_body_size")]
+    pub max_body_size: u64,
+
+    #[serde(default = "GlobalConfig::default_shutdown_grace_period")]
+    pub shutdown_grace_period: u64,
+
+    #[serde(default = "GlobalConfig::default_command_grace_period")]
+    pub command_grace_period: u64,
+
+    #[serde(default = "GlobalConfig::default_tls")]
+    pub tls: Option<TlsConfig>,
+
+    #[serde(default = "GlobalConfig::default_listeners")]
+    pub listeners: Vec<ListenerConfig>,
+
+    /// proxies whose forwarding header tells the client address
+    #[serde(default = "GlobalConfig::default_trusted_proxies")]
+    pub trusted_proxies: Vec<String>,
+
+    /// the header set by the trusted proxies: `forwarded`, `x-forwarded-for` or `x-real-ip`
+    #[serde(default = "GlobalConfig::default_forwarded_header")]
+    pub forwarded_header: String,
+
+    /// limits how often each client address can send requests, to any hook
+    #[serde(default = "GlobalConfig::default_client_rate_limit")]
+    pub client_rate_limit: Option<RateLimitConfig>,
 }
 
 impl Default for GlobalConfig {
@@ -30,6 +89,22 @@ impl GlobalConfig {
             log_prefix: Self::default_log_prefix(), 
             log_level: Self::default_log_level(),
             rules_dir: Self::default_rules_dir(),
+            keep_alive_timeout: Self::default_keep_alive_timeout(),
+            max_requests_per_connection: Self::default_max_requests_per_connection(),
+            read_timeout: Self::default_read_timeout(),
+            header_timeout: Self::default_header_timeout(),
+            body_timeout: Self::default_body_timeout(),
+            write_timeout: Self::default_write_timeout(),
+            max_header_size: Self::default_max_header_size(),
+            max_header_count: Self::default_max_header_count(),
+            max_body_size: Self::default_max_body_size(),
+            shutdown_grace_period: Self::default_shutdown_grace_period(),
+            command_grace_period: Self::default_command_grace_period(),
+            tls: Self::default_tls(),
+            listeners: Self::default_listeners(),
+            trusted_proxies: Self::default_trusted_proxies(),
+            forwarded_header: Self::default_forwarded_header(),
+            client_rate_limit: Self::default_client_rate_limit(),
         }
     }
 
@@ -49,6 +124,121 @@ impl GlobalConfig {
         String::from("rules")
     }
 
+    pub fn default_keep_alive_timeout() -> u64{
+        5
+    }
+
+    pub fn default_max_requests_per_connection() -> usize{
+        100
+    }
+
+    pub fn default_read_timeout() -> u64{
+        30
+    }
+
+    pub fn default_header_timeout() -> Option<u64>{
+        None
+    }
+
+    pub fn default_body_timeout() -> Option<u64>{
+        None
+    }
+
+    pub fn default_write_timeout() -> u64{
+        30
+    }
+
+    pub fn default_max_header_size() -> u64{
+        16384
+    }
+
+    pub fn default_max_header_count() -> usize{
+        100
+    }
+
+    pub fn default_max_body_size() -> u64{
+        2097152
+    }
+
+    pub fn default_shutdown_grace_period() -> u64{
+        30
+    }
+
+    pub fn default_command_grace_period() -> u64{
+        0
+    }
+
+    pub fn default_tls() -> Option<TlsConfig>{
+        None
+    }
+
+    pub fn default_listeners() -> Vec<ListenerConfig>{
+        Vec::new()
+    }
+
+    pub fn default_trusted_proxies() -> Vec<String>{
+        Vec::new()
+    }
+
+    pub fn default_forwarded_header() -> String{
+        String::from("x-forwarded-for")
+    }
+
+    pub fn default_client_rate_limit() -> Option<RateLimitConfig>{
+        None
+    }
+
+    /// idle time to wait for the next request on a persistent connection,
+    /// `None` means waiting forever
+    pub fn get_keep_alive_timeout(&self) -> Option<Duration>{
+        match self.keep_alive_timeout {
+            0 => None,
+            t => Some(Duration::from_secs(t)),
+        }
+    }
+
+    /// time limit for receiving the header section of a request
+    pub fn get_header_timeout(&self) -> Option<Duration>{
+        match self.header_timeout.unwrap_or(self.read_timeout) {
+            0 => None,
+            t => Some(Duration::from_secs(t)),
+        }
+    }
+
+    /// time limit for receiving the body of a request
+    pub fn get_body_timeout(&self) -> Option<Duration>{
+        match self.body_timeout.unwrap_or(self.read_timeout) {
+            0 => None,
+            t => Some(Duration::from_secs(t)),
+        }
+    }
+
+    /// time limit for sending a response
+    pub fn get_write_timeout(&self) -> Option<Duration>{
+        match self.write_timeout {
+            0 => None,
+            t => Some(Duration::from_secs(t)),
+        }
+    }
+
+    /// time to wait for the in-flight requests to finish on shutdown
+    pub fn get_shutdown_grace_period(&self) -> Duration{
+        Duration::from_secs(self.shutdown_grace_period)
+    }
+
+    /// time to wait for the running hook commands to exit on shutdown
+    pub fn get_command_grace_period(&self) -> Duration{
+        Duration::from_secs(self.command_grace_period)
+    }
+
+    pub fn get_forwarded_header(&self) -> Result<ForwardedHeader, String>{
+        ForwardedHeader::new(&self.forwarded_header)
+    }
+
+    pub fn get_trusted_proxies(&self) -> Result<TrustedProxies, String>{
+        TrustedProxies::new(&self.trusted_proxies, self.get_forwarded_header()?)
+    }
+
     pub fn get_log_path(&self) -> String{
         let log_prefix = match &self.log_prefix{
             Some(p) => p,
@@ -0,0 +1,45 @@
+use serde::{Serialize, Deserialize};
+
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+pub struct ListenerConfig {
+    #[serde(default = "ListenerConfig::default_name")]
+    pub name: String,
+
+    /// `ip:port` for a tcp listener, or `unix:/path/to/socket` for a unix domain socket
+    pub address: String,
+
+    #[serde(default = "ListenerConfig::default_tls")]
+    pub tls: bool,
+
+    /// expect a PROXY protocol header from a load balancer at the start of every connection
+    #[serde(default = "ListenerConfig::default_proxy_protocol")]
+    pub proxy_protocol: bool,
+}
+
+impl ListenerConfig {
+    pub fn new(name: &str, address: &str) -> Self{
+        ListenerConfig {
+            name: name.to_string(),
+            address: address.to_string(),
+            tls: Self::default_tls(),
+            proxy_protocol: Self::default_proxy_protocol(),
+        }
+    }
+
+    pub fn default_name() -> String{
+        String::from("default")
+    }
+
+    pub fn default_tls() -> bool{
+        false
+    }
+
+    pub fn default_proxy_protocol() -> bool{
+        false
+    }
+
+    /// path of the socket file if this is a unix domain socket listener
+    pub fn get_unix_path(&self) -> Option<&str>{
+        self.address.strip_prefix("unix:")
+    }
+}
@@ -4,9 +4,15 @@ use std::{collections::HashMap, env};
 
 use crate::rule::Rule;
 
+use self::cors::CorsConfig;
 use self::global::GlobalConfig;
+use self::rate_limit::RateLimitConfig;
 pub mod configs;
+pub mod cors;
 pub mod global;
+pub mod listener;
+pub mod rate_limit;
+pub mod tls;
 
 type RespondHeader = HashMap<String, String>;
 type CommandArgument = HashMap<String, String>;
@@ -42,7 +48,30 @@ pub struct Config {
     pub log_level: String,
 
     #[serde(default = "Config::default_trigger_rules")]
-    pub trigger_rules: Option<TriggerRules>
+    pub trigger_rules: Option<TriggerRules>,
+
+    #[serde(default = "Config::default_listeners")]
+    pub listeners: Vec<String>,
+
+    /// overrides the global `max_body_size` for this hook
+    #[serde(default = "Config::default_max_body_size")]
+    pub max_body_size: Option<u64>,
+
+    /// compute the HMAC of a compressed body over its decoded bytes instead of the bytes received
+    #[serde(default = "Config::default_hmac_over_decoded_body")]
+    pub hmac_over_decoded_body: bool,
+
+    /// limits how often the hook is triggered, whoever sends the requests
+    #[serde(default = "Config::default_rate_limit")]
+    pub rate_limit: Option<RateLimitConfig>,
+
+    /// the methods the hook can be triggered with, other methods are answered with `405`
+    #[serde(default = "Config::default_http_methods")]
+    pub http_methods: Vec<String>,
+
+    /// allows browsers to call the hook from other origins
+    #[serde(default = "Config::default_cors")]
+    pub cors: Option<CorsConfig>,
 }
 
 
@@ -65,6 +94,12 @@ impl Config {
             log_prefix: None,
             log_level: String::from("Info"),
             trigger_rules: None,
+            listeners: Vec::new(),
+            max_body_size: None,
+            hmac_over_decoded_body: false,
+            rate_limit: None,
+            http_methods: Self::default_http_methods(),
+            cors: Self::default_cors(),
         }
     }
     
@@ -132,8 +167,95 @@ impl Config {
         None
     }
 
+    pub fn default_listeners() -> Vec<String>{
+        Vec::new()
+    }
+
+    pub fn default_max_body_size() -> Option<u64>{
+        None
+    }
+
+    pub fn default_hmac_over_decoded_body() -> bool{
+        false
+    }
+
+    pub fn default_rate_limit() -> Option<RateLimitConfig>{
+        None
+    }
+
+    pub fn default_http_methods() -> Vec<String>{
+        vec![String::from("GET"), String::from("POST")]
+    }
+
+    pub fn default_cors() -> Option<CorsConfig>{
+        None
+    }
+
+    /// The methods a request to the hook is answered to, for the `Allow` header.
+    /// `HEAD` comes with `GET`, and `OPTIONS` is always answered.
+    pub fn get_allowed_methods(&self) -> Vec<String>{
+        let mut methods: Vec<String> = self.http_methods.iter().map(|m| m.trim().to_uppercase()).collect();
+        if methods.iter().any(|m| m == "GET") {
+            methods.push(String::from("HEAD"));
+        }
+        methods.push(String::from("OPTIONS"));
+        let mut allowed = Vec::new();
+        for method in methods{
+            if !allowed.contains(&method) {
+                allowed.push(method);
+            }
+        }
+        allowed
+    }
+
+    /// whether a request with the method is answered, methods are case-sensitive
+    pub fn allows_method(&self, method: &str) -> bool{
+        self.get_allowed_methods().iter().any(|m| m == method)
+    }
+
+    /// `http_methods` must be method names, e.g. `POST`
+    pub fn validate_http_methods(&self) -> Result<(), String>{
+        if self.http_methods.is_empty() {
+            return Err("http_methods must not be empty".to_string());
+        }
+        for method in self.http_methods.iter(){
+            let method = method.trim();
+            if method.is_empty() || !method.chars().all(|c| c.is_ascii_alphabetic() || c == '-' || c == '_') {
+                return Err(format!("Invalid http method [{}]", method));
+            }
+        }
+        Ok(())
+    }
+
+    /// whether the hook is served on the named listener,
+    /// a hook without `listeners` is served on all of them
+    pub fn is_served_on(&self, listener: &str) -> bool{
+        self.listeners.is_empty() || self.listeners.iter().any(|l| l == listener)
+    }
+
     pub fn get_trigger_rule(&self) -> Option<Rule>{
         self.trigger_rules.as_ref().map(Rule::new)
     }
 }
 
+#[test]
+fn test_allowed_methods(){
+    let mut config = Config::new();
+    assert_eq!(vec!["GET", "POST", "HEAD", "OPTIONS"], config.get_allowed_methods());
+    assert!(config.allows_method("HEAD"));
+    assert!(!config.allows_method("DELETE"));
+    assert!(!config.allows_method("TRACE"));
+    assert!(!config.allows_method("post"));
+
+    config.http_methods = vec!["post".to_string(), "PUT".to_string()];
+    assert_eq!(vec!["POST", "PUT", "OPTIONS"], config.get_allowed_methods());
+    assert!(!config.allows_method("GET"));
+    assert!(!config.allows_method("HEAD"));
+    assert!(config.validate_http_methods().is_ok());
+
+    config.http_methods = vec!["GET POST".to_string()];
+    assert!(config.validate_http_methods().is_err());
+    config.http_methods = Vec::new();
+    assert!(config.validate_http_methods().is_err());
+}
+
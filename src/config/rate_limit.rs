@@ -0,0 +1,49 @@
+use serde::{Serialize, Deserialize};
+
+/// A token bucket: `requests` are allowed every `period` seconds on average,
+/// and up to `burst` of them at once
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone, Copy)]
+pub struct RateLimitConfig {
+    pub requests: u32,
+
+    #[serde(default = "RateLimitConfig::default_period")]
+    pub period: u64,
+
+    /// the size of the bucket, the same as `requests` if not specified
+    #[serde(default = "RateLimitConfig::default_burst")]
+    pub burst: Option<u32>,
+}
+
+impl RateLimitConfig {
+    pub fn new(requests: u32, period: u64) -> Self{
+        RateLimitConfig {
+            requests,
+            period,
+            burst: Self::default_burst(),
+        }
+    }
+
+    pub fn default_period() -> u64{
+        60
+    }
+
+    pub fn default_burst() -> Option<u32>{
+        None
+    }
+
+    pub fn get_burst(&self) -> u32{
+        self.burst.unwrap_or(self.requests)
+    }
+
+    /// tokens added to the bucket per second
+    pub fn get_refill_rate(&self) -> f64{
+        self.requests as f64 / self.period as f64
+    }
+
+    pub fn validate(&self) -> Result<(), String>{
+        if self.requests == 0 || self.period == 0 || self.get_burst() == 0 {
+            return Err(format!("Invalid rate limit {:?}: requests, period and burst must be positive", self));
+        }
+        Ok(())
+    }
+}
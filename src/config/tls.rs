@@ -0,0 +1,52 @@
+use std::time::Duration;
+
+use serde::{Serialize, Deserialize};
+
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+pub struct TlsConfig {
+    pub cert_file: String,
+
+    pub key_file: String,
+
+    #[serde(default = "TlsConfig::default_reload_interval")]
+    pub reload_interval: u64,
+
+    #[serde(default = "TlsConfig::default_client_ca_file")]
+    pub client_ca_file: Option<String>,
+
+    #[serde(default = "TlsConfig::default_client_cert_required")]
+    pub client_cert_required: bool,
+}
+
+impl TlsConfig {
+    pub fn new(cert_file: &str, key_file: &str) -> Self{
+        TlsConfig {
+            cert_file: cert_file.to_string(),
+            key_file: key_file.to_string(),
+            reload_interval: Self::default_reload_interval(),
+            client_ca_file: Self::default_client_ca_file(),
+            client_cert_required: Self::default_client_cert_required(),
+        }
+    }
+
+    pub fn default_reload_interval() -> u64{
+        0
+    }
+
+    pub fn default_client_ca_file() -> Option<String>{
+        None
+    }
+
+    pub fn default_client_cert_required() -> bool{
+        true
+    }
+
+    /// how often the certificate files are checked for changes,
+    /// `None` means the certificate is never reloaded
+    pub fn get_reload_interval(&self) -> Option<Duration>{
+        match self.reload_interval {
+            0 => None,
+            t => Some(Duration::from_secs(t)),
+        }
+    }
+}
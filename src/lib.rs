@@ -1,59 +1,279 @@
-use std::{
-    io::{prelude::*, BufReader}, net::TcpStream
-};
+use std::io::{self, Write};
+use tokio::{io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader}, time::Instant};
 pub mod config;
 pub mod arguments;
 pub mod check;
 pub mod command;
 pub mod parser;
+pub mod proxy;
+pub mod ratelimit;
+pub mod request;
 pub mod response;
 pub mod mylog;
+pub mod multipart;
+pub mod server;
+pub mod shutdown;
+pub mod stream;
+pub mod systemd;
+pub mod tls;
 mod rule;
-use config::configs::CONFIGS;
+use config::{Config, configs::CONFIGS};
 use check::*;
 use command::*;
-use parser::{parse_http_header, parse_hook_id_from_url};
-use response::{http_response_with_err, respond_with_favicon};
+use parser::parse_http_header;
+use request::{HttpRequest, forwarded::TRUSTED_PROXIES};
+use ratelimit::{check_client_rate_limit, check_hook_rate_limit};
+use response::cors::{cors_headers, insert_response_headers};
+use response::{http_response_with_err, respond_with_favicon, respond_with_head, respond_with_method_not_allowed, respond_with_options, respond_with_too_many_requests, strip_response_body};
+use shutdown::Shutdown;
+use stream::{Peer, with_deadline, with_timeout};
 
-use crate::parser::{parse_http_body, merge_http_request};
+use crate::parser::{parse_http_body, parse_chunked_body, is_chunked};
+
+/// Serve the requests sent over a connection, the header of the first one is to be read
+/// by `header_deadline`, which starts when the connection is accepted
+pub async fn handle_connection<S>(mut stream: S, peer: Peer, mut shutdown: Shutdown, header_deadline: Option<Instant>) -> Result<(), String>
+where S: AsyncRead + AsyncWrite + Unpin{   
+    let keep_alive_timeout = CONFIGS.global.get_keep_alive_timeout();
+    let header_timeout = CONFIGS.global.get_header_timeout();
+    let body_timeout = CONFIGS.global.get_body_timeout();
+    let write_timeout = CONFIGS.global.get_write_timeout();
+    // the peer address from whom the requests are sent
+    let peer_addr = &peer.address;
+    let max_requests = CONFIGS.global.max_requests_per_connection;
 
-pub fn handle_connection(mut stream: TcpStream) -> Result<(), String>{   
     let mut reader = BufReader::new(&mut stream);
-    // Get the http request header from tcpstream
-    let http_header = parse_http_header(reader.by_ref());
-    
-    let mut body: Option<String> = None;
-    // Get the http request body from the TcpStream for POST request
-    if http_header.get("Method").unwrap() == "POST"{
-        let content_length: usize = http_header.get("Content-Length").unwrap().parse().unwrap();
-        body = parse_http_body(reader.by_ref(), content_length);
+    let mut served_requests: usize = 0;
+    loop {
+        // Close the connection if the peer keeps it idle for too long,
+        // or the server is shutting down while waiting for the next request.
+        // A new connection is given no longer than the header deadline to send its first request.
+        let idle_deadline = match served_requests {
+            0 => header_deadline,
+            _ => keep_alive_timeout.map(|t| Instant::now() + t),
+        };
+        let next_request = tokio::select! {
+            biased;
+            r = with_deadline(idle_deadline, reader.fill_buf()) => r.map(|buf| !buf.is_empty()),
+            _ = shutdown.triggered() => Ok(false),
+        };
+        match next_request{
+            Ok(true) => (),
+            Ok(false) => break,
+            Err(e) => {
+                log::debug!("Connection with {} finished: {}", peer_addr, e);
+                break;
+            }
+        }
+
+        // Get the http request header from the stream
+        let header = parse_http_header(&mut reader, &peer, CONFIGS.global.max_header_size, CONFIGS.global.max_header_count);
+        // the header of a later request is given the header timeout from its first byte
+        let deadline = match served_requests {
+            0 => header_deadline,
+            _ => header_timeout.map(|t| Instant::now() + t),
+        };
+        let mut http_request = match with_deadline(deadline, header).await{
+            Ok(h) => h,
+            // the rest of the header is never read, so the connection cannot be reused
+            Err(e) if is_reportable_err(&e) => {
+                log::error!("Failed to read header from {}: {}", peer_addr, e);
+                let mut http_request = HttpRequest::new("GET", "/", "HTTP/1.1", peer.clone());
+                respond_with_err_and_close(reader.get_mut(), &e, &mut http_request, write_timeout).await;
+                break;
+            },
+            Err(e) => {
+                log::debug!("Connection with {} finished: {}", peer_addr, e);
+                break;
+            }
+        };
+        served_requests += 1;
+        http_request.client_ip = TRUSTED_PROXIES.resolve_client_ip(peer_addr, &http_request.headers);
+        match http_request.client_ip {
+            Some(ip) => log::info!("{} {} from {} via {}", http_request.method, http_request.url(), ip, peer_addr),
+            None => log::info!("{} {} from {}", http_request.method, http_request.url(), peer_addr),
+        }
+
+        // Ask the client to close the connection once the limit is reached,
+        // or the server is shutting down
+        if served_requests >= max_requests || shutdown.is_triggered(){
+            http_request.headers.set("Connection", "close");
+        }
+
+        // Get the http request body from the stream if there is one(RFC 9112 §6.3).
+        // `Transfer-Encoding` takes precedence over `Content-Length`, and a body whose length
+        // cannot be told, i.e. `chunked` is not its last coding, is rejected.
+        let max_body_size = CONFIGS.get_max_body_size(&http_request.hook_id());
+        let body = if http_request.headers.contains("Transfer-Encoding"){
+            if !is_chunked(&http_request.headers){
+                log::warn!("Invalid Transfer-Encoding [{}] from {}", http_request.headers.get("Transfer-Encoding").unwrap_or_default(), peer_addr);
+                Err(io::Error::new(io::ErrorKind::InvalidData, "Invalid Transfer-Encoding"))
+            } else {
+                // a proxy in front may have framed the request by `Content-Length` instead,
+                // so nothing after it on the connection can be trusted
+                if http_request.headers.contains("Content-Length"){
+                    http_request.headers.set("Connection", "close");
+                }
+                with_timeout(body_timeout, parse_chunked_body(&mut reader, &mut http_request.trailers, max_body_size)).await
+            }
+        } else if let Some(content_length) = http_request.headers.get("Content-Length"){
+            match content_length.parse::<u64>(){
+                Ok(l) => with_timeout(body_timeout, parse_http_body(&mut reader, l, max_body_size)).await,
+                Err(e) => {
+                    log::warn!("Invalid Content-Length [{}] from {}: {}", content_length, peer_addr, e);
+                    Err(io::Error::new(io::ErrorKind::InvalidData, "Invalid Content-Length"))
+                }
+            }
+        } else {
+            Ok(None)
+        };
+        // a compressed body is decoded here, so that its size is limited before anything parses it
+        let body = body.and_then(|b| {
+            http_request.body = b;
+            http_request.decode_body(max_body_size)
+        });
+        match body{
+            Ok(()) => (),
+            // the rest of the body may be left unread, so the connection cannot be reused
+            Err(e) if is_reportable_err(&e) => {
+                log::error!("Failed to read body from {}: {}", peer_addr, e);
+                respond_with_err_and_close(reader.get_mut(), &e, &mut http_request, write_timeout).await;
+                break;
+            },
+            Err(e) => {
+                log::debug!("Connection with {} finished: {}", peer_addr, e);
+                break;
+            }
+        };
+
+        // the hook is handled on the blocking thread pool, since checking and spawning the command,
+        // reading rule files and saving uploads all block, and would otherwise stall the workers
+        // serving every other connection. The response is buffered before sending.
+        let listener = peer.listener.clone();
+        let handled = tokio::task::spawn_blocking(move || {
+            let mut response = Vec::new();
+            handle_request(&mut response, &http_request, &listener);
+            (response, http_request)
+        });
+        let (mut response, http_request) = match handled.await {
+            Ok(r) => r,
+            Err(e) => {
+                log::error!("Failed to handle request from {}: {}", peer_addr, e);
+                break;
+            }
+        };
+        if http_request.method == "HEAD" {
+            strip_response_body(&mut response);
+        }
+        if let Err(e) = with_timeout(write_timeout, reader.get_mut().write_all(&response)).await{
+            log::warn!("Failed to send response to {}: {}", peer_addr, e);
+            break;
+        }
+        if !http_request.is_keep_alive(){
+            break;
+        }
+    }
+    // flush the pending data, and notify the peer that the connection is closing
+    if let Err(e) = with_timeout(write_timeout, stream.shutdown()).await{
+        log::debug!("Failed to shutdown connection with {}: {}", peer_addr, e);
     }
-    // Get the peer address from whom the request was sent
-    let peer_addr = reader.by_ref().get_ref().peer_addr().unwrap();
+    Ok(())
+}
+
+/// Whether a failure to read a request is told to the client before closing the connection,
+/// rather than the connection being just closed, e.g. when the client is gone
+fn is_reportable_err(e: &io::Error) -> bool{
+    matches!(
+        e.kind(),
+        io::ErrorKind::InvalidData
+        | io::ErrorKind::FileTooLarge
+        | io::ErrorKind::Unsupported
+        | io::ErrorKind::ArgumentListTooLong
+        | io::ErrorKind::TimedOut
+    )
+}
 
-    // build a completed http request map
-    let http_request = merge_http_request(&http_header, &body, &peer_addr);
+async fn respond_with_err_and_close<W>(stream: &mut W, e: &io::Error, http_request: &mut HttpRequest, write_timeout: Option<std::time::Duration>)
+where W: AsyncWrite + Unpin{
+    http_request.headers.set("Connection", "close");
+    let mut response = Vec::new();
+    http_response_with_err(&mut response, e, http_request, None);
+    let _ = with_timeout(write_timeout, stream.write_all(&response)).await;
+}
 
-    if parse_hook_id_from_url(http_request.get("Url").unwrap()) == "favicon.ico"{
-        respond_with_favicon(&mut stream);
-        return Ok(());
+fn handle_request(stream: &mut impl Write, http_request: &HttpRequest, listener: &str){
+    if http_request.hook_id() == "favicon.ico"{
+        respond_with_favicon(stream, http_request);
+        return;
+    }
+    // a client sending too many requests is turned away before anything else
+    if let Err(wait) = check_client_rate_limit(http_request.client_ip) {
+        log::warn!("Rate limit of client [{}] exceeded, request to hook [{}] rejected", format_client_ip(http_request), http_request.hook_id());
+        respond_with_too_many_requests(stream, http_request, retry_after_secs(wait));
+        return;
     }
     // check if the id in request defined in configs 
-    if let Err(e) = is_webhook_id_in_configs(&http_request) {
-        http_response_with_err(&mut stream, &e, &http_request, None);
-        return Ok(());
+    if let Err(e) = is_webhook_id_in_configs(http_request) {
+        http_response_with_err(stream, &e, http_request, None);
+        return;
     };
 
     // get the right config
-    let config = CONFIGS.get_config_by_http_request(&http_request);
+    let config = CONFIGS.get_config_by_http_request(http_request);
+
+    // a hook restricted to other listeners is hidden on this one
+    if let Err(e) = is_webhook_served_on_listener(&config, listener) {
+        http_response_with_err(stream, &e, http_request, None);
+        return;
+    };
+
+    // every response of a hook allowing cross-origin requests carries the CORS headers,
+    // and a CORS preflight is answered by the OPTIONS response along with them
+    let mut response = Vec::new();
+    handle_hook_request(&mut response, &config, http_request);
+    insert_response_headers(&mut response, &cors_headers(&config, http_request));
+    stream.write_all(&response).unwrap();
+}
+
+fn handle_hook_request(stream: &mut impl Write, config: &Config, http_request: &HttpRequest){
+    if !config.allows_method(&http_request.method) {
+        log::warn!("Method [{}] not allowed for hook [{}]", http_request.method, config.id);
+        respond_with_method_not_allowed(stream, http_request, &config.get_allowed_methods());
+        return;
+    }
+    // neither OPTIONS nor HEAD triggers the hook
+    if http_request.method == "OPTIONS" {
+        respond_with_options(stream, http_request, &config.get_allowed_methods());
+        return;
+    }
 
     // preflight check according to the found config
-    if let Err(e) = preflight_check(&config, &http_request){
-        http_response_with_err(&mut stream, &e, &http_request, None);
-        return Ok(());
+    if let Err(e) = preflight_check(config, http_request){
+        http_response_with_err(stream, &e, http_request, None);
+        return;
     };
 
+    if http_request.method == "HEAD" {
+        respond_with_head(stream, http_request, config);
+        return;
+    }
+
+    // only the deliveries that would trigger the hook count against its rate limit
+    if let Err(wait) = check_hook_rate_limit(&config.id) {
+        log::warn!("Rate limit of hook [{}] exceeded, request from client [{}] rejected", config.id, format_client_ip(http_request));
+        respond_with_too_many_requests(stream, http_request, retry_after_secs(wait));
+        return;
+    }
+
     // generate response and send
-    trigger_hook(&mut stream, &config, &http_request);
-    Ok(())
-}
\ No newline at end of file
+    trigger_hook(stream, config, http_request);
+}
+
+fn format_client_ip(http_request: &HttpRequest) -> String{
+    http_request.client_ip.map(|ip| ip.to_string()).unwrap_or_else(|| "unknown".to_string())
+}
+
+/// Whole seconds for `Retry-After`, rounded up so that a retry is not rejected again
+fn retry_after_secs(wait: std::time::Duration) -> u64{
+    wait.as_secs_f64().ceil().max(1.0) as u64
+}
@@ -1,19 +1,19 @@
-use std::net::TcpListener;
-use std::env;
+use std::{env, os::fd::OwnedFd, time::Duration};
 extern crate lazy_static;
 
-use rusthook::{config::configs::{CONFIGS}, mylog::set_global_logger};
+use rusthook::{command::wait_for_running_commands, config::{configs::CONFIGS, listener::ListenerConfig, tls::TlsConfig}, mylog::set_global_logger, server::{serve, BoundListener, ConnectionLimits}, shutdown::{shutdown_channel, wait_for_signal}, systemd::{take_listen_fds, Notifier}, tls::build_server_config};
+use tokio_rustls::TlsAcceptor;
 
 use clap::Parser;
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 struct Args {
-   /// the ip on which the server is listening
+   /// the ip on which the server is listening, if no listeners are configured
    #[arg(short, long, default_value_t = String::from("0.0.0.0"))]
    ip: String,
 
-   /// the port on which the server is listening
+   /// the port on which the server is listening, if no listeners are configured
    #[arg(short, long, default_value_t = String::from("7878"))]
    port: String,
 
@@ -25,32 +25,131 @@ struct Args {
    #[arg(short, long, default_value_t = 4)]
    threads: usize,
 
+   /// max number of connections served at the same time
+   #[arg(short, long, default_value_t = 1024)]
+   max_connections: usize,
+
+   /// max number of accepted connections waiting for a running one to finish,
+   /// more are answered with 503 Service Unavailable
+   #[arg(long, default_value_t = 1024)]
+   max_queued_connections: usize,
+
+   /// seconds in the Retry-After header of a 503 Service Unavailable response
+   #[arg(long, default_value_t = 5)]
+   retry_after: u64,
+
    /// stack size for each thread
    #[arg(short, long, default_value_t = 4_000_000)]
    stack_size: usize,
+
+   /// certificate file(PEM) for serving https, overrides the global tls config
+   #[arg(long, requires = "tls_key")]
+   tls_cert: Option<String>,
+
+   /// private key file(PEM) for serving https, overrides the global tls config
+   #[arg(long, requires = "tls_cert")]
+   tls_key: Option<String>,
+
+   /// seconds between checks for certificate changes, 0 disables reloading
+   #[arg(long)]
+   tls_reload_interval: Option<u64>,
+
+   /// CA bundle file(PEM) to verify client certificates against, enables mutual tls
+   #[arg(long)]
+   tls_client_ca: Option<String>,
+}
+
+impl Args {
+    fn get_tls_config(&self) -> Option<TlsConfig>{
+        let mut tls = match (&self.tls_cert, &self.tls_key) {
+            (Some(cert), Some(key)) => Some(TlsConfig::new(cert, key)),
+            _ => CONFIGS.global.tls.clone(),
+        };
+        if let (Some(t), Some(interval)) = (tls.as_mut(), self.tls_reload_interval){
+            t.reload_interval = interval;
+        }
+        if let (Some(t), Some(ca)) = (tls.as_mut(), &self.tls_client_ca){
+            t.client_ca_file = Some(ca.to_string());
+        }
+        tls
+    }
+
+    /// listeners in the global config take precedence over the sockets passed by systemd,
+    /// which take precedence over `--ip` and `--port`
+    fn get_listener_configs(&self, tls_enabled: bool, listen_fds: &[(String, OwnedFd)]) -> Vec<ListenerConfig>{
+        if !CONFIGS.global.listeners.is_empty(){
+            return CONFIGS.global.listeners.clone();
+        }
+        if !listen_fds.is_empty(){
+            return listen_fds.iter().map(|(name, _)| {
+                let mut listener = ListenerConfig::new(name, &format!("systemd:{}", name));
+                listener.tls = tls_enabled;
+                listener
+            }).collect();
+        }
+        let mut listener = ListenerConfig::new("default", &format!("{}:{}", &self.ip, &self.port));
+        listener.tls = tls_enabled;
+        vec![listener]
+    }
 }
 
 fn main() {
     let args = Args::parse();
-    let listener = TcpListener::bind(format!("{}:{}", &args.ip, &args.port)).unwrap();
-
-    let pool = threadpool::Builder::new()
-    .num_threads(args.threads)
-    .thread_name("conn".into())
-    .thread_stack_size(args.stack_size).build();
     env::set_var("CONFIG_PATH", &args.config);
 
     //set a global logger
     let global_logger_path = CONFIGS.global.get_log_path();
     set_global_logger(&global_logger_path, CONFIGS.global.get_log_level().unwrap());
 
-    for stream in listener.incoming() {
-        let stream = stream.unwrap();
-        pool.execute(||{
-            let _ = rusthook::handle_connection(stream);
-        });
-    }
-    
-    pool.join();
-}
+    // take the systemd environment before any other thread is started
+    let notifier = Notifier::from_env();
+    let mut listen_fds = take_listen_fds();
 
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+    .worker_threads(args.threads)
+    .thread_name("conn")
+    .thread_stack_size(args.stack_size)
+    .enable_all()
+    .build()
+    .unwrap();
+
+    let tls_acceptor = args.get_tls_config().map(|tls| TlsAcceptor::from(build_server_config(&tls).unwrap()));
+    let listener_configs = args.get_listener_configs(tls_acceptor.is_some(), &listen_fds);
+
+    runtime.block_on(async {
+        let listeners = listener_configs
+            .iter()
+            .map(|l| {
+                // a socket passed by systemd is matched by its `FileDescriptorName=`
+                let bound = match listen_fds.iter().position(|(name, _)| name == &l.name) {
+                    Some(i) => BoundListener::from_fd(l, listen_fds.remove(i).1, tls_acceptor.as_ref()),
+                    None => BoundListener::bind(l, tls_acceptor.as_ref()),
+                };
+                bound.unwrap_or_else(|e| panic!("Cannot bind listener [{}] on {}: {}", l.name, l.address, e))
+            })
+            .collect();
+        for (name, _) in listen_fds.drain(..){
+            log::warn!("Socket [{}] passed by systemd matches no listener, closing it", name);
+        }
+        let (trigger, shutdown) = shutdown_channel();
+        let limits = ConnectionLimits {
+            max_connections: args.max_connections,
+            max_queued_connections: args.max_queued_connections,
+            retry_after: args.retry_after,
+        };
+        let server = tokio::spawn(serve(listeners, limits, shutdown));
+        notifier.notify("READY=1");
+        wait_for_signal().await;
+        notifier.notify("STOPPING=1");
+        trigger.trigger();
+        let _ = server.await;
+    });
+
+    // a hook being handled on the blocking thread pool of a closed connection may still start
+    // its command, which is to be waited for as well
+    runtime.shutdown_timeout(Duration::from_secs(5));
+
+    // hook commands are not killed on exit, give them a chance to finish first
+    let running = wait_for_running_commands(CONFIGS.global.get_command_grace_period());
+    log::info!("Rusthook stopped with {} hook command(s) still running", running);
+}
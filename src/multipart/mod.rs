@@ -0,0 +1,213 @@
+use std::{fs, io, os::unix::fs::DirBuilderExt, path::{Path, PathBuf}, sync::atomic::{AtomicU64, Ordering}};
+
+use crate::request::HttpRequest;
+
+/// A part of a `multipart/form-data` body
+#[derive(Debug, PartialEq, Clone)]
+pub struct Part {
+    /// the form field name of the part
+    pub name: String,
+    /// the file name sent by the client, `None` for a text field
+    pub filename: Option<String>,
+    pub content_type: Option<String>,
+    pub data: Vec<u8>,
+}
+
+/// Split a `multipart/form-data` body into its parts
+pub fn parse_multipart(body: &[u8], boundary: &str) -> Result<Vec<Part>, io::Error>{
+    let dash_boundary = format!("--{}", boundary).into_bytes();
+    let delimiter = [b"\r\n".as_slice(), &dash_boundary].concat();
+
+    // anything before the first boundary is a preamble to be ignored
+    let mut pos = match find(body, &dash_boundary) {
+        Some(p) => p + dash_boundary.len(),
+        None => return Err(invalid_multipart("Missing Boundary")),
+    };
+    let mut parts = Vec::new();
+    loop {
+        if body[pos..].starts_with(b"--"){
+            break;
+        }
+        if !body[pos..].starts_with(b"\r\n"){
+            return Err(invalid_multipart("Invalid Boundary Line"));
+        }
+        pos += 2;
+        let end = match find(&body[pos..], &delimiter) {
+            Some(e) => pos + e,
+            None => return Err(invalid_multipart("Missing Closing Boundary")),
+        };
+        parts.push(parse_part(&body[pos..end])?);
+        pos = end + delimiter.len();
+    }
+    Ok(parts)
+}
+
+fn parse_part(part: &[u8]) -> Result<Part, io::Error>{
+    // a part without header fields starts with the empty line right away
+    let (header, data) = match part.strip_prefix(b"\r\n") {
+        Some(data) => (&b""[..], data),
+        None => match find(part, b"\r\n\r\n") {
+            Some(p) => (&part[..p], &part[p + 4..]),
+            None => return Err(invalid_multipart("Invalid Part Header")),
+        },
+    };
+    let header = String::from_utf8_lossy(header);
+
+    let mut name = None;
+    let mut filename = None;
+    let mut content_type = None;
+    for line in header.split("\r\n"){
+        let (key, value) = match line.split_once(':') {
+            Some((k, v)) => (k.trim(), v.trim()),
+            None => continue,
+        };
+        if key.eq_ignore_ascii_case("Content-Disposition"){
+            name = get_header_param(value, "name");
+            filename = get_header_param(value, "filename");
+        } else if key.eq_ignore_ascii_case("Content-Type"){
+            content_type = Some(value.to_string());
+        }
+    }
+    match name {
+        Some(name) => Ok(Part { name, filename, content_type, data: data.to_vec() }),
+        None => Err(invalid_multipart("Missing Part Name")),
+    }
+}
+
+/// Get a parameter of a header value like `form-data; name="field"`
+pub fn get_header_param(value: &str, param: &str) -> Option<String>{
+    value
+        .split(';')
+        .skip(1)
+        .filter_map(|p| p.split_once('='))
+        .find(|(k, _)| k.trim().eq_ignore_ascii_case(param))
+        .map(|(_, v)| v.trim().trim_matches('"').to_string())
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize>{
+    haystack.windows(needle.len()).position(|w| w == needle)
+}
+
+fn invalid_multipart(reason: &str) -> io::Error{
+    log::warn!("Invalid multipart body: {}", reason);
+    io::Error::new(io::ErrorKind::InvalidData, "Invalid Multipart Body")
+}
+
+/// Files of a request saved to a temporary directory, which is removed on drop
+#[derive(Debug)]
+pub struct UploadedFiles {
+    dir: PathBuf,
+    /// the form field name and the saved path of each file
+    files: Vec<(String, PathBuf)>,
+}
+
+static UPLOAD_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+impl UploadedFiles {
+    /// Save the file parts of a `multipart/form-data` request to a new directory under `base_dir`
+    pub fn save(http_request: &HttpRequest, base_dir: &Path) -> Result<Self, io::Error>{
+        let dir = base_dir.join(format!(
+            "rusthook-upload-{}-{}",
+            std::process::id(),
+            UPLOAD_COUNTER.fetch_add(1, Ordering::Relaxed),
+        ));
+        fs::DirBuilder::new().mode(0o700).create(&dir)?;
+        let mut uploads = UploadedFiles { dir, files: Vec::new() };
+
+        let parts = http_request.multipart().unwrap_or_default();
+        for (i, part) in parts.iter().enumerate(){
+            let filename = match &part.filename {
+                Some(f) => f,
+                None => continue,
+            };
+            let path = uploads.dir.join(format!("{}-{}", i, upload_file_name(filename, &part.name)));
+            fs::write(&path, &part.data)?;
+            uploads.files.push((part.name.clone(), path));
+        }
+        log::info!("{} uploaded file(s) saved under {}", uploads.files.len(), uploads.dir.display());
+        Ok(uploads)
+    }
+
+    /// The saved path of the first file sent in the form field
+    pub fn get_path(&self, name: &str) -> Option<&Path>{
+        self.files.iter().find(|(n, _)| n == name).map(|(_, p)| p.as_path())
+    }
+
+    pub fn dir(&self) -> &Path{
+        &self.dir
+    }
+}
+
+/// The name to save an uploaded file as. Never trusting the path sent by the client,
+/// only the last component of the file name is kept, or else that of the field name,
+/// with `\\` taken as a separator too since some clients send Windows paths.
+fn upload_file_name(filename: &str, field_name: &str) -> String{
+    [filename, field_name]
+        .iter()
+        .filter_map(|name| name.rsplit(['/', '\\']).next())
+        .find(|name| !name.is_empty() && *name != "." && *name != "..")
+        .unwrap_or("upload")
+        .to_string()
+}
+
+impl Drop for UploadedFiles {
+    fn drop(&mut self){
+        if let Err(e) = fs::remove_dir_all(&self.dir){
+            log::warn!("Failed to remove uploaded files under {}: {}", self.dir.display(), e);
+        }
+    }
+}
+
+#[cfg(test)]
+const TEST_BODY: &[u8] = b"preamble\r\n\
+--XyZ\r\n\
+Content-Disposition: form-data; name=\"text\"\r\n\
+\r\n\
+build passed\r\n\
+--XyZ\r\n\
+Content-Disposition: form-data; name=\"artifact\"; filename=\"../../etc/report.bin\"\r\n\
+Content-Type: application/octet-stream\r\n\
+\r\n\
+\x00\xff\r\n--X\r\n\
+--XyZ--\r\n";
+
+#[test]
+fn test_parse_multipart(){
+    let parts = parse_multipart(TEST_BODY, "XyZ").unwrap();
+    assert_eq!(2, parts.len());
+    assert_eq!("text", parts[0].name);
+    assert_eq!(None, parts[0].filename);
+    assert_eq!(b"build passed".to_vec(), parts[0].data);
+    assert_eq!(Some("../../etc/report.bin".to_string()), parts[1].filename);
+    assert_eq!(Some("application/octet-stream".to_string()), parts[1].content_type);
+    assert_eq!(b"\x00\xff\r\n--X".to_vec(), parts[1].data);
+
+    assert!(parse_multipart(b"--XyZ\r\nContent-Disposition: form-data; name=\"a\"\r\n\r\nno end", "XyZ").is_err());
+}
+
+#[test]
+fn test_save_uploaded_files(){
+    let mut request = HttpRequest::new("POST", "/", "HTTP/1.1", crate::request::test_peer());
+    request.headers.append("Content-Type", "multipart/form-data; boundary=\"XyZ\"");
+    request.body = Some(TEST_BODY.to_vec());
+
+    let uploads = UploadedFiles::save(&request, &std::env::temp_dir()).unwrap();
+    let path = uploads.get_path("artifact").unwrap().to_path_buf();
+    assert_eq!(uploads.dir(), path.parent().unwrap());
+    assert_eq!(b"\x00\xff\r\n--X".to_vec(), fs::read(&path).unwrap());
+    assert_eq!(None, uploads.get_path("text"));
+
+    let dir = uploads.dir().to_path_buf();
+    drop(uploads);
+    assert!(!dir.exists());
+}
+
+#[test]
+fn test_upload_file_name(){
+    assert_eq!("report.bin", upload_file_name("../../etc/report.bin", "artifact"));
+    assert_eq!("shot.png", upload_file_name("C:\\Users\\ci\\shot.png", "artifact"));
+    // a file name without a last component falls back to the field name, which is reduced as well
+    assert_eq!("artifact", upload_file_name("", "artifact"));
+    assert_eq!("passwd", upload_file_name("..", "../../etc/passwd"));
+    assert_eq!("upload", upload_file_name("/", "a/.."));
+}
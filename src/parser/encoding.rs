@@ -0,0 +1,74 @@
+use std::io::{self, Read};
+
+use flate2::read::{DeflateDecoder, GzDecoder, ZlibDecoder};
+
+use super::body_too_large;
+
+/// Undo the codings listed in a `Content-Encoding` value, in the reverse order they are applied.
+/// The decoded body must not grow larger than `max_body_size`, so that a small compressed body
+/// cannot expand into an arbitrarily large one.
+pub fn decode_content(body: &[u8], content_encoding: &str, max_body_size: u64) -> Result<Vec<u8>, io::Error>{
+    let mut decoded = body.to_vec();
+    for coding in content_encoding.rsplit(',').map(|c| c.trim().to_lowercase()){
+        decoded = match coding.as_str() {
+            "" | "identity" => continue,
+            "gzip" | "x-gzip" => read_limited(GzDecoder::new(decoded.as_slice()), max_body_size)?,
+            // `deflate` is meant to be a zlib stream, but some senders send a raw deflate one
+            "deflate" => match read_limited(ZlibDecoder::new(decoded.as_slice()), max_body_size) {
+                Err(e) if e.kind() == io::ErrorKind::InvalidData => read_limited(DeflateDecoder::new(decoded.as_slice()), max_body_size)?,
+                r => r?,
+            },
+            c => {
+                log::warn!("Unsupported Content-Encoding [{}]", c);
+                return Err(io::Error::new(io::ErrorKind::Unsupported, "Unsupported Content-Encoding"));
+            }
+        };
+    }
+    Ok(decoded)
+}
+
+fn read_limited(decoder: impl Read, max_body_size: u64) -> Result<Vec<u8>, io::Error>{
+    let mut decoded = Vec::new();
+    // one more byte than allowed is enough to tell that the limit is exceeded
+    if let Err(e) = decoder.take(max_body_size + 1).read_to_end(&mut decoded){
+        log::warn!("Failed to decode body: {}", e);
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "Invalid Content Encoding"));
+    }
+    if decoded.len() as u64 > max_body_size{
+        return Err(body_too_large(max_body_size));
+    }
+    Ok(decoded)
+}
+
+#[test]
+fn test_decode_content(){
+    use std::io::Write;
+    use flate2::{write::{GzEncoder, ZlibEncoder, DeflateEncoder}, Compression};
+
+    let body = b"{\"ref\":\"refs/heads/main\"}";
+    let mut gzip = GzEncoder::new(Vec::new(), Compression::default());
+    gzip.write_all(body).unwrap();
+    let gzip = gzip.finish().unwrap();
+    assert_eq!(body.to_vec(), decode_content(&gzip, "gzip", 1024).unwrap());
+
+    let mut zlib = ZlibEncoder::new(Vec::new(), Compression::default());
+    zlib.write_all(body).unwrap();
+    assert_eq!(body.to_vec(), decode_content(&zlib.finish().unwrap(), "Deflate", 1024).unwrap());
+    let mut raw = DeflateEncoder::new(Vec::new(), Compression::default());
+    raw.write_all(body).unwrap();
+    assert_eq!(body.to_vec(), decode_content(&raw.finish().unwrap(), "deflate", 1024).unwrap());
+
+    // codings are undone from the last one applied
+    let mut twice = GzEncoder::new(Vec::new(), Compression::default());
+    twice.write_all(&gzip).unwrap();
+    assert_eq!(body.to_vec(), decode_content(&twice.finish().unwrap(), "gzip, identity, gzip", 1024).unwrap());
+
+    let mut bomb = GzEncoder::new(Vec::new(), Compression::best());
+    bomb.write_all(&vec![0; 1 << 20]).unwrap();
+    let bomb = bomb.finish().unwrap();
+    assert!(bomb.len() < 4096);
+    assert_eq!(io::ErrorKind::FileTooLarge, decode_content(&bomb, "gzip", 4096).unwrap_err().kind());
+
+    assert_eq!(io::ErrorKind::InvalidData, decode_content(body, "gzip", 1024).unwrap_err().kind());
+    assert_eq!(io::ErrorKind::Unsupported, decode_content(body, "br", 1024).unwrap_err().kind());
+}
@@ -1,77 +1,264 @@
-use std::{collections::HashMap, io::{BufReader, BufRead, Read}, net::{TcpStream, SocketAddr}};
+use std::io;
+use percent_encoding::percent_decode_str;
 use serde_json::Value;
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncReadExt};
 
-pub fn parse_peer_address(reader: &mut BufReader<&mut TcpStream>) -> String{
-    reader.get_ref().peer_addr().unwrap().to_string()
-}
+use crate::{request::{Headers, HttpRequest}, stream::Peer};
+
+pub mod encoding;
+pub mod xml;
+
+/// The longest line of a chunked body other than the data, i.e. a chunk size line or a trailer field
+const MAX_CHUNK_LINE_SIZE: u64 = 8192;
 
-pub fn parse_http_header(reader: &mut BufReader<&mut TcpStream>) -> HashMap<String, String>{
-    let http_header: Vec<_> = reader
-        .lines()
-        .map(|result| result.unwrap())
-        .take_while(|line| !line.is_empty())
-        .collect();
+/// Read the request line and the header section of a request, the body is left in the reader.
+/// The header section, including the request line, is limited to `max_header_size` bytes
+/// and `max_header_count` fields, either of them exceeded is reported as `ArgumentListTooLong`.
+pub async fn parse_http_header<R: AsyncBufRead + Unpin>(reader: &mut R, peer: &Peer, max_header_size: u64, max_header_count: usize) -> Result<HttpRequest, io::Error>{
+    let mut http_header: Vec<String> = Vec::new();
+    let mut header_size: u64 = 0;
+    loop {
+        let line = match read_http_line(reader, max_header_size.saturating_sub(header_size)).await?{
+            Some(l) => l,
+            // the peer closed the connection before sending another request
+            None => return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "Connection Closed By Peer",
+            )),
+        };
+        header_size += line.len() as u64 + 2;
+        // empty lines before the request line are ignored
+        if line.is_empty() && http_header.is_empty(){
+            continue;
+        }
+        if line.is_empty(){
+            break;
+        }
+        if http_header.len() > max_header_count{
+            return Err(header_too_large(&format!("more than {} header fields", max_header_count)));
+        }
+        http_header.push(line);
+    }
 
     let request_0: Vec<&str> = http_header[0].split(' ').collect();
-    let mut header_map: HashMap<String, String> = HashMap::from([
-        ("Method".to_string(), request_0[0].to_string()),
-        ("Url".to_string(), request_0[1].to_string()),
-        ("Version".to_string(), request_0[2].to_string()),
-    ]);
+    if request_0.len() != 3{
+        let err_msg = format!("Invalid request line: {}", http_header[0]);
+        log::warn!("{}", err_msg);
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "Invalid Request Line",
+        ));
+    }
+    let mut http_request = HttpRequest::new(request_0[0], request_0[1], request_0[2], peer.clone());
 
     for item in http_header[1..].iter(){
-        let request: Vec<&str>= item.split(": ").collect();
-        let key = request[0].to_string();
-        let value = request[1..].join(": ").to_string();
-        header_map.entry(key).or_insert(value);
+        http_request.headers.append_line(item)?;
     }
-    header_map
+    Ok(http_request)
+}
+
+/// Check whether the request body is framed by `Transfer-Encoding: chunked`,
+/// which is so only if `chunked` is the last coding applied (RFC 9112 §6.3)
+pub fn is_chunked(headers: &Headers) -> bool{
+    headers
+        .get_all("Transfer-Encoding")
+        .iter()
+        .flat_map(|v| v.split(','))
+        .map(|t| t.trim())
+        .rfind(|t| !t.is_empty())
+        .is_some_and(|t| t.eq_ignore_ascii_case("chunked"))
 }
 
-pub fn parse_http_body(reader: &mut BufReader<&mut TcpStream>, content_length: usize) -> Option<String>{
-    if content_length > 2097152{
-        let err_msg = "maximum content-length(2M) exceeded";
-        log::error!("{}", err_msg);
-        panic!("{}", err_msg);
+/// Header fields that are never taken from a trailer section, as they frame, route or describe
+/// the body, or tell where the request comes from
+const FORBIDDEN_TRAILERS: [&str; 11] = [
+    "Transfer-Encoding", "Content-Length", "Content-Encoding", "Content-Type", "Host", "Connection",
+    "Trailer", "TE", "Upgrade", "Forwarded", "X-Real-IP",
+];
+
+fn is_forbidden_trailer(name: &str) -> bool{
+    FORBIDDEN_TRAILERS.iter().any(|f| f.eq_ignore_ascii_case(name))
+        || name.len() >= 12 && name[..12].eq_ignore_ascii_case("X-Forwarded-")
+}
+
+/// Decode a body sent with `Transfer-Encoding: chunked`, no more than `max_body_size` bytes.
+/// Trailer fields following the last chunk are appended to `trailers`, except the forbidden ones.
+pub async fn parse_chunked_body<R: AsyncBufRead + Unpin>(reader: &mut R, trailers: &mut Headers, max_body_size: u64) -> Result<Option<Vec<u8>>, io::Error>{
+    let mut buf: Vec<u8> = Vec::new();
+    loop {
+        let size_line = read_chunk_line(reader).await?;
+        // chunk extensions after `;` are ignored
+        let size_str = size_line.split(';').next().unwrap_or_default().trim();
+        let size = match u64::from_str_radix(size_str, 16){
+            Ok(n) => n,
+            Err(e) => {
+                let err_msg = format!("Invalid chunk size [{}]: {}", size_str, e);
+                log::warn!("{}", err_msg);
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "Invalid Chunk Size",
+                ));
+            }
+        };
+        if size == 0{
+            break;
+        }
+        // compared against what is left, since a huge size would overflow the sum
+        if size > max_body_size.saturating_sub(buf.len() as u64){
+            return Err(body_too_large(max_body_size));
+        }
+        let read = reader.take(size).read_to_end(&mut buf).await?;
+        if read as u64 != size{
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "Incomplete Chunk",
+            ));
+        }
+        // every chunk is terminated by CRLF
+        if !read_chunk_line(reader).await?.is_empty(){
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Invalid Chunk Terminator",
+            ));
+        }
     }
-    let mut buf = [0u8; 2097152];
-    let mut res = reader.take(2097152);
-    let _= res.read(&mut buf);
-    let http_body = std::str::from_utf8(&buf[..content_length]).unwrap().to_string();
-    Some(http_body)
-}
-
-pub fn merge_http_request(header: &HashMap<String, String>, body: &Option<String>, peer_addr: &SocketAddr) -> HashMap<String, String>{
-    let mut request = match body {
-        None => header.clone(),
-        Some(s) => {
-            let mut request:HashMap<String, String> = HashMap::new();
-            request.clone_from(header);
-            request.entry("Body".to_string()).or_insert(s.to_string());
-            request 
+
+    // trailer section, terminated by an empty line, and counted towards the body size
+    let mut size = buf.len() as u64;
+    loop {
+        let line = read_chunk_line(reader).await?;
+        if line.is_empty(){
+            break;
         }
-    };
-    request.entry("Peer-Address".to_string()).or_insert(peer_addr.to_string());
-    request
+        size += line.len() as u64;
+        if size > max_body_size{
+            return Err(body_too_large(max_body_size));
+        }
+        let mut field = Headers::new();
+        field.append_line(&line)?;
+        for (name, value) in field.iter(){
+            match is_forbidden_trailer(name) {
+                true => log::warn!("Trailer field [{}] dropped", name),
+                false => trailers.append(name, value),
+            }
+        }
+    }
+    Ok(Some(buf))
+}
+
+async fn read_chunk_line<R: AsyncBufRead + Unpin>(reader: &mut R) -> Result<String, io::Error>{
+    match read_http_line(reader, MAX_CHUNK_LINE_SIZE).await {
+        Ok(Some(line)) => Ok(line),
+        Ok(None) => Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "Incomplete Chunked Body",
+        )),
+        Err(e) if e.kind() == io::ErrorKind::ArgumentListTooLong => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "Chunk Line Too Long",
+        )),
+        Err(e) => Err(e),
+    }
+}
+
+/// Read a line terminated by LF or CRLF without the terminator, `None` on EOF.
+/// A line longer than `limit` bytes, including the terminator, is not read any further.
+async fn read_http_line<R: AsyncBufRead + Unpin>(reader: &mut R, limit: u64) -> Result<Option<String>, io::Error>{
+    let mut line = Vec::new();
+    let read = (&mut *reader).take(limit).read_until(b'\n', &mut line).await?;
+    if read == 0 && limit > 0{
+        return Ok(None);
+    }
+    if !line.ends_with(b"\n"){
+        if read as u64 == limit{
+            return Err(header_too_large(&format!("line longer than {} bytes", limit)));
+        }
+        return Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "Incomplete Line",
+        ));
+    }
+    let line = String::from_utf8_lossy(&line).to_string();
+    Ok(Some(line.trim_end_matches('\n').trim_end_matches('\r').to_string()))
+}
+
+fn header_too_large(reason: &str) -> io::Error{
+    log::warn!("Request header too large: {}", reason);
+    io::Error::new(
+        io::ErrorKind::ArgumentListTooLong,
+        "Request Header Fields Too Large",
+    )
+}
+
+/// Read a body of `content_length` bytes, which is refused before reading if it exceeds `max_body_size`
+pub async fn parse_http_body<R: AsyncBufRead + Unpin>(reader: &mut R, content_length: u64, max_body_size: u64) -> Result<Option<Vec<u8>>, io::Error>{
+    if content_length > max_body_size{
+        return Err(body_too_large(max_body_size));
+    }
+    // read exactly `content_length` bytes as they arrive, so that a pipelined
+    // request following this one stays in the reader
+    let mut buf = Vec::new();
+    let read = reader.take(content_length).read_to_end(&mut buf).await?;
+    if read as u64 != content_length{
+        return Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "Incomplete Body",
+        ));
+    }
+    Ok(Some(buf))
+}
+
+fn body_too_large(max_body_size: u64) -> io::Error{
+    let err_msg = format!("maximum body size({} bytes) exceeded", max_body_size);
+    log::warn!("{}", err_msg);
+    io::Error::new(
+        io::ErrorKind::FileTooLarge,
+        "Payload Too Large",
+    )
 }
 
+/// The hook id of a request target, which is the percent-decoded path without the surrounding slashes
 pub fn parse_hook_id_from_url(url: &str) -> String{
-    let patterns: Vec<&str> = url.split('?').collect();
-    patterns[0].trim_matches('/').to_string()
-}
-
-pub fn parse_parameters_from_url(url: &str) -> HashMap<String, String>{
-    let patterns: Vec<&str> = url.split('?').collect();
-    let parameters = patterns[1..].join("?").to_string();
-    let mut params_map = HashMap::new();
-    let params_vec: Vec<&str> = parameters.split('&').collect();
-    for param in params_vec{
-        let param_vec: Vec<&str> = param.split('=').collect();
-        let key = param_vec[0].to_string();
-        let val = param_vec[1].to_string();
-        params_map.entry(key).or_insert(val);
+    let path = url.split('?').next().unwrap_or_default();
+    percent_decode_str(path).decode_utf8_lossy().trim_matches('/').to_string()
+}
+
+/// The decoded parameters of the query of a request target, in the order they are sent.
+/// A repeated key is kept once per value, and a key without `=` has an empty value.
+pub fn parse_parameters_from_url(url: &str) -> Vec<(String, String)>{
+    let query = match url.split_once('?') {
+        Some((_, q)) => q,
+        None => return Vec::new(),
+    };
+    form_urlencoded::parse(query.as_bytes())
+        .map(|(k, v)| (k.into_owned(), v.into_owned()))
+        .collect()
+}
+
+/// Decode an `application/x-www-form-urlencoded` body into a JSON object
+pub fn parse_form_urlencoded(body: &[u8]) -> Value{
+    form_fields_to_json(form_urlencoded::parse(body).map(|(k, v)| (k.into_owned(), v.into_owned())))
+}
+
+/// Collect form fields into a JSON object,
+/// the values of a field sent more than once are collected into an array
+pub fn form_fields_to_json<I: IntoIterator<Item = (String, String)>>(fields: I) -> Value{
+    let mut form = serde_json::Map::new();
+    for (key, value) in fields{
+        insert_field(&mut form, key, Value::String(value));
+    }
+    Value::Object(form)
+}
+
+/// Insert a field into a JSON object, a field inserted more than once becomes an array of the values
+fn insert_field(map: &mut serde_json::Map<String, Value>, key: String, value: Value){
+    match map.get_mut(&key){
+        None => {
+            map.insert(key, value);
+        },
+        Some(Value::Array(values)) => values.push(value),
+        Some(first) => *first = Value::Array(vec![first.take(), value]),
     }
-    params_map
 }
 
 pub fn get_item_from_json(v: &Value, item: &str) -> Option<String>{
@@ -88,7 +275,7 @@ pub fn get_item_from_json(v: &Value, item: &str) -> Option<String>{
                         val = &val[i];
                     }
                 } else {
-                    break Some(val.as_str().unwrap().to_string())
+                    break val.as_str().map(|s| s.to_string())
                 }
             }  
         }
@@ -101,22 +288,22 @@ pub fn get_item_from_json(v: &Value, item: &str) -> Option<String>{
     }
 }
 
-pub fn get_payload_item_from_http_request(item: &str, http_request: &HashMap<String, String>) -> Option<String>{
-    match http_request.get("Body"){
-        Some(payload) =>  {
-            if item == "entire-payload" {
-                Some(payload.to_string())
-            }else{
-                let v: Value = serde_json::from_str(payload).unwrap();
-                get_item_from_json(&v, item)
-            }
-        },
-        None => None
+pub fn get_payload_item_from_http_request(item: &str, http_request: &HttpRequest) -> Option<String>{
+    // the body is converted to text only when it is needed as a whole
+    if item == "entire-payload" {
+        return http_request.body_str().map(|p| p.to_string());
     }
+    let v = http_request.payload()?;
+    get_item_from_json(&v, item)
+}
+
+/// Get the value of a header, `select` picks among the values of a repeated header
+pub fn get_header_from_http_request(name: &str, select: &str, http_request: &HttpRequest) -> Option<String>{
+    http_request.headers.select(name, select)
 }
 
-pub fn get_header_from_http_request(name: &str, http_request: &HashMap<String, String>) -> Option<String>{
-    http_request.get(name).cloned()
+pub fn get_trailer_from_http_request(name: &str, select: &str, http_request: &HttpRequest) -> Option<String>{
+    http_request.trailers.select(name, select)
 }
 
 #[test]
@@ -151,16 +338,158 @@ pub fn test_get_item_direct(){
 fn test_get_hook_id_from_http_request_url(){
     let id = parse_hook_id_from_url("/hook/?y=1&u=2");
     assert_eq!(String::from("hook"), id);
+    let id = parse_hook_id_from_url("/deploy%20web+app/");
+    assert_eq!(String::from("deploy web+app"), id);
 }
 
 #[test]
 fn test_parse_parameters_from_url(){
     let url = "/hooks/?x=1&y=2&z=aaa";
     let res = parse_parameters_from_url(url);
-    let exp = HashMap::from([
+    let exp = vec![
         ("x".to_string(), "1".to_string()),
         ("y".to_string(), "2".to_string()),
         ("z".to_string(), "aaa".to_string()),
-    ]);
+    ];
     assert_eq!(res, exp);
+
+    let res = parse_parameters_from_url("/hooks?flag&a=b=c&t=web+prod%2F1&t=&&%E2%9C%93");
+    let exp = vec![
+        ("flag".to_string(), "".to_string()),
+        ("a".to_string(), "b=c".to_string()),
+        ("t".to_string(), "web prod/1".to_string()),
+        ("t".to_string(), "".to_string()),
+        ("\u{2713}".to_string(), "".to_string()),
+    ];
+    assert_eq!(res, exp);
+    assert!(parse_parameters_from_url("/hooks").is_empty());
+}
+
+#[tokio::test]
+async fn test_parse_chunked_body(){
+    let raw = "4\r\nWiki\r\n7;ext=1\r\npedia i\r\nB\r\nn \r\nchunks.\r\n0\r\nX-Checksum: abc\r\nContent-Encoding: br\r\nx-forwarded-for: 10.0.0.1\r\n\r\nGET / HTTP/1.1\r\n";
+    let mut reader = std::io::Cursor::new(raw.as_bytes());
+    let mut headers = Headers::new();
+    headers.append("Transfer-Encoding", "chunked");
+    assert!(is_chunked(&headers));
+    let mut gzip_last = Headers::new();
+    gzip_last.append("Transfer-Encoding", "chunked");
+    gzip_last.append("Transfer-Encoding", "gzip");
+    assert!(!is_chunked(&gzip_last));
+    gzip_last.set("Transfer-Encoding", "gzip, chunked");
+    assert!(is_chunked(&gzip_last));
+    let mut trailers = Headers::new();
+    let body = parse_chunked_body(&mut reader, &mut trailers, 1024).await.unwrap();
+    assert_eq!(Some(b"Wikipedia in \r\nchunks.".to_vec()), body);
+    assert_eq!(Some("abc".to_string()), trailers.get("X-Checksum"));
+    assert_eq!(1, trailers.iter().count());
+    assert!(!headers.contains("X-Checksum"));
+
+    // the next pipelined request is left untouched
+    let mut rest = String::new();
+    reader.read_line(&mut rest).await.unwrap();
+    assert_eq!("GET / HTTP/1.1\r\n", rest);
+}
+
+#[tokio::test]
+async fn test_parse_chunked_body_invalid(){
+    let mut headers = Headers::new();
+    let mut reader = std::io::Cursor::new("zz\r\nabc\r\n0\r\n\r\n".as_bytes());
+    assert!(parse_chunked_body(&mut reader, &mut headers, 1024).await.is_err());
+    let mut reader = std::io::Cursor::new("5\r\nabc".as_bytes());
+    assert!(parse_chunked_body(&mut reader, &mut headers, 1024).await.is_err());
+    let raw = format!("{}\r\n", "0".repeat(10_000));
+    let mut reader = std::io::Cursor::new(raw.as_bytes());
+    assert_eq!(io::ErrorKind::InvalidData, parse_chunked_body(&mut reader, &mut headers, 1024).await.unwrap_err().kind());
+    let raw = format!("1\r\na\r\n0\r\n{}\r\n", "X-Pad: 0123456789\r\n".repeat(100));
+    let mut reader = std::io::Cursor::new(raw.as_bytes());
+    assert_eq!(io::ErrorKind::FileTooLarge, parse_chunked_body(&mut reader, &mut headers, 1024).await.unwrap_err().kind());
+    // a huge chunk size after a non-empty chunk is rejected rather than read until EOF
+    let raw = format!("1\r\na\r\nffffffffffffffff\r\n{}", "a".repeat(2048));
+    let mut reader = std::io::Cursor::new(raw.as_bytes());
+    assert_eq!(io::ErrorKind::FileTooLarge, parse_chunked_body(&mut reader, &mut headers, 1024).await.unwrap_err().kind());
+}
+
+#[tokio::test]
+async fn test_parse_http_header(){
+    let raw = "POST /hook?x=1 HTTP/1.1\r\nBody: spoofed\r\nX-Tag: a\r\nx-tag: b\r\n\r\n";
+    let mut reader = std::io::Cursor::new(raw.as_bytes());
+    let request = parse_http_header(&mut reader, &crate::request::test_peer(), 8192, 100).await.unwrap();
+    assert_eq!("POST", request.method);
+    assert_eq!("/hook", request.path);
+    assert_eq!(Some("x=1".to_string()), request.query);
+    assert_eq!(vec!["a", "b"], request.headers.get_all("X-Tag"));
+    // a header named after a request property is just a header
+    assert_eq!(Some("spoofed".to_string()), request.headers.get("Body"));
+    assert_eq!(None, request.body);
+}
+
+#[tokio::test]
+async fn test_parse_http_header_fields(){
+    let peer = crate::request::test_peer();
+    // the space after the colon is optional, and so a body framed without it is not missed
+    let raw = "POST /hook HTTP/1.1\r\nContent-Length:27\r\nX-Url: http://a:b@c \t\r\n\r\n";
+    let request = parse_http_header(&mut std::io::Cursor::new(raw.as_bytes()), &peer, 8192, 100).await.unwrap();
+    assert_eq!(Some("27".to_string()), request.headers.get("Content-Length"));
+    assert_eq!(Some("http://a:b@c".to_string()), request.headers.get("X-Url"));
+
+    for raw in [
+        "POST /hook HTTP/1.1\r\nContent-Length :27\r\n\r\n",
+        "POST /hook HTTP/1.1\r\n Content-Length: 27\r\n\r\n",
+        "POST /hook HTTP/1.1\r\n: 27\r\n\r\n",
+        "POST /hook HTTP/1.1\r\nContent-Length 27\r\n\r\n",
+    ]{
+        let err = parse_http_header(&mut std::io::Cursor::new(raw.as_bytes()), &peer, 8192, 100).await.unwrap_err();
+        assert_eq!(io::ErrorKind::InvalidData, err.kind());
+    }
+}
+
+#[tokio::test]
+async fn test_parse_http_header_limits(){
+    let peer = crate::request::test_peer();
+    let raw = "\r\nGET / HTTP/1.1\r\nA: 1\r\nB: 2\r\n\r\n";
+    let request = parse_http_header(&mut std::io::Cursor::new(raw.as_bytes()), &peer, 8192, 2).await.unwrap();
+    assert_eq!(Some("2".to_string()), request.headers.get("B"));
+
+    let err = parse_http_header(&mut std::io::Cursor::new(raw.as_bytes()), &peer, 8192, 1).await.unwrap_err();
+    assert_eq!(io::ErrorKind::ArgumentListTooLong, err.kind());
+    let err = parse_http_header(&mut std::io::Cursor::new(raw.as_bytes()), &peer, 24, 100).await.unwrap_err();
+    assert_eq!(io::ErrorKind::ArgumentListTooLong, err.kind());
+
+    // a line never terminated is not buffered beyond the limit
+    let long = format!("GET /{} HTTP/1.1", "a".repeat(100_000));
+    let err = parse_http_header(&mut std::io::Cursor::new(long.as_bytes()), &peer, 8192, 100).await.unwrap_err();
+    assert_eq!(io::ErrorKind::ArgumentListTooLong, err.kind());
+    let err = parse_http_header(&mut std::io::Cursor::new("GET / HTTP/1.1\r\nA: 1".as_bytes()), &peer, 8192, 100).await.unwrap_err();
+    assert_eq!(io::ErrorKind::UnexpectedEof, err.kind());
+}
+
+#[tokio::test]
+async fn test_parse_binary_body(){
+    let raw: &[u8] = &[0xff, 0x00, 0xfe, 0x80, b'G', b'E', b'T'];
+    let mut reader = std::io::Cursor::new(raw);
+    let body = parse_http_body(&mut reader, 4, 1024).await.unwrap();
+    assert_eq!(Some(vec![0xff, 0x00, 0xfe, 0x80]), body);
+}
+
+#[tokio::test]
+async fn test_body_too_large(){
+    let mut reader = std::io::Cursor::new("0123456789".as_bytes());
+    let err = parse_http_body(&mut reader, 10, 4).await.unwrap_err();
+    assert_eq!(io::ErrorKind::FileTooLarge, err.kind());
+    // refused before reading anything
+    assert_eq!(0, reader.position());
+
+    let mut reader = std::io::Cursor::new("3\r\nabc\r\n3\r\ndef\r\n0\r\n\r\n".as_bytes());
+    let err = parse_chunked_body(&mut reader, &mut Headers::new(), 4).await.unwrap_err();
+    assert_eq!(io::ErrorKind::FileTooLarge, err.kind());
+}
+
+#[test]
+fn test_parse_form_urlencoded(){
+    let form = parse_form_urlencoded(b"text=hello+world%21&user.id=U%2F1&tag=a&tag=b");
+    assert_eq!(Some("hello world!".to_string()), get_item_from_json(&form, "text"));
+    assert_eq!(Some("U/1".to_string()), get_item_from_json(&form, "user.id"));
+    assert_eq!(Some("b".to_string()), get_item_from_json(&form, "tag.1"));
+    assert_eq!(None, get_item_from_json(&form, "missing.field"));
 }
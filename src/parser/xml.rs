@@ -0,0 +1,119 @@
+use std::io;
+
+use quick_xml::{events::{BytesStart, Event}, Reader};
+use serde_json::{Map, Value};
+
+use super::insert_field;
+
+/// An element being read, with its attributes and child elements collected so far
+struct Element {
+    name: String,
+    fields: Map<String, Value>,
+    text: String,
+}
+
+/// Convert an XML document into a JSON object, so that elements and attributes
+/// can be addressed by the same dotted names as a JSON payload:
+/// + an element is keyed by its name under its parent, the root element under the top object
+/// + an attribute is keyed by its name prefixed with `@`
+/// + an element with neither attributes nor child elements becomes its text,
+///   otherwise its text, if any, is keyed by `#text`
+/// + an element repeated under the same parent becomes an array
+pub fn parse_xml(body: &[u8]) -> Result<Value, io::Error>{
+    let mut reader = Reader::from_reader(body);
+    reader.config_mut().trim_text(true);
+
+    let mut root = Map::new();
+    let mut stack: Vec<Element> = Vec::new();
+    let mut buf = Vec::new();
+    loop {
+        match reader.read_event_into(&mut buf).map_err(invalid_xml)? {
+            Event::Start(e) => stack.push(start_element(&e)?),
+            Event::Empty(e) => {
+                let element = start_element(&e)?;
+                end_element(element, &mut stack, &mut root);
+            },
+            Event::Text(e) => {
+                if let Some(top) = stack.last_mut(){
+                    top.text.push_str(&e.unescape().map_err(invalid_xml)?);
+                }
+            },
+            Event::CData(e) => {
+                if let Some(top) = stack.last_mut(){
+                    top.text.push_str(&String::from_utf8_lossy(&e));
+                }
+            },
+            Event::End(_) => match stack.pop() {
+                Some(element) => end_element(element, &mut stack, &mut root),
+                None => return Err(invalid_xml("Unexpected End Tag")),
+            },
+            Event::Eof => break,
+            // declarations, comments and processing instructions carry no values
+            _ => (),
+        }
+        buf.clear();
+    }
+    if !stack.is_empty() || root.is_empty(){
+        return Err(invalid_xml("Incomplete Document"));
+    }
+    Ok(Value::Object(root))
+}
+
+fn start_element(e: &BytesStart) -> Result<Element, io::Error>{
+    let mut fields = Map::new();
+    for attr in e.attributes(){
+        let attr = attr.map_err(invalid_xml)?;
+        let key = format!("@{}", String::from_utf8_lossy(attr.key.as_ref()));
+        let value = attr.unescape_value().map_err(invalid_xml)?.to_string();
+        fields.insert(key, Value::String(value));
+    }
+    Ok(Element {
+        name: String::from_utf8_lossy(e.name().as_ref()).to_string(),
+        fields,
+        text: String::new(),
+    })
+}
+
+fn end_element(element: Element, stack: &mut [Element], root: &mut Map<String, Value>){
+    let Element { name, mut fields, text } = element;
+    let value = if fields.is_empty(){
+        Value::String(text)
+    } else {
+        if !text.is_empty(){
+            fields.insert("#text".to_string(), Value::String(text));
+        }
+        Value::Object(fields)
+    };
+    match stack.last_mut() {
+        Some(parent) => insert_field(&mut parent.fields, name, value),
+        None => insert_field(root, name, value),
+    }
+}
+
+fn invalid_xml<E: ToString>(e: E) -> io::Error{
+    log::warn!("Invalid XML payload: {}", e.to_string());
+    io::Error::new(io::ErrorKind::InvalidData, "Invalid XML Payload")
+}
+
+#[test]
+fn test_parse_xml(){
+    use super::get_item_from_json;
+
+    let body = br#"<?xml version="1.0"?>
+        <notification type="PROBLEM">
+            <host name="db-1" state="DOWN"/>
+            <service>disk</service>
+            <service>load</service>
+            <output><![CDATA[load > 10 & rising]]></output>
+            <comment author="ops">checked &amp; acked</comment>
+        </notification>"#;
+    let v = parse_xml(body).unwrap();
+    assert_eq!(Some("PROBLEM".to_string()), get_item_from_json(&v, "notification.@type"));
+    assert_eq!(Some("db-1".to_string()), get_item_from_json(&v, "notification.host.@name"));
+    assert_eq!(Some("load".to_string()), get_item_from_json(&v, "notification.service.1"));
+    assert_eq!(Some("load > 10 & rising".to_string()), get_item_from_json(&v, "notification.output"));
+    assert_eq!(Some("checked & acked".to_string()), get_item_from_json(&v, "notification.comment.#text"));
+
+    assert!(parse_xml(b"<a><b></a>").is_err());
+    assert!(parse_xml(b"not xml").is_err());
+}
@@ -0,0 +1,144 @@
+use std::{io, net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr}};
+
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+/// The signature starting a PROXY protocol v2 header
+const V2_SIGNATURE: [u8; 12] = *b"\r\n\r\n\0\r\nQUIT\n";
+/// The longest v1 header allowed by the specification, including the trailing CRLF
+const V1_MAX_LENGTH: usize = 107;
+
+/// Read the PROXY protocol header, v1 or v2, sent by a load balancer at the start of a connection,
+/// and return the address of the client it proxies.
+/// `None` is returned for a connection the load balancer opens on its own, e.g. for health checks.
+/// Nothing after the header is read, so the stream can be handed over to tls or http right away.
+pub async fn read_proxy_header<R: AsyncRead + Unpin>(stream: &mut R) -> Result<Option<SocketAddr>, io::Error>{
+    let mut start = [0; 6];
+    stream.read_exact(&mut start).await?;
+    if &start == b"PROXY " {
+        return read_v1_header(stream).await;
+    }
+    if start == V2_SIGNATURE[..6] {
+        return read_v2_header(stream).await;
+    }
+    Err(invalid_proxy_header("Missing PROXY Protocol Header"))
+}
+
+async fn read_v1_header<R: AsyncRead + Unpin>(stream: &mut R) -> Result<Option<SocketAddr>, io::Error>{
+    // the header is read byte by byte, since the bytes after it belong to the proxied connection
+    let mut line = b"PROXY ".to_vec();
+    while !line.ends_with(b"\r\n") {
+        if line.len() >= V1_MAX_LENGTH {
+            return Err(invalid_proxy_header("PROXY Protocol Header Too Long"));
+        }
+        line.push(stream.read_u8().await?);
+    }
+    let line = String::from_utf8_lossy(&line[..line.len() - 2]).to_string();
+    parse_v1_header(&line)
+}
+
+fn parse_v1_header(line: &str) -> Result<Option<SocketAddr>, io::Error>{
+    let fields: Vec<&str> = line.split(' ').collect();
+    match fields.get(1) {
+        Some(&"UNKNOWN") => return Ok(None),
+        Some(&"TCP4") | Some(&"TCP6") if fields.len() == 6 => (),
+        _ => return Err(invalid_proxy_header("Invalid PROXY Protocol Header")),
+    }
+    let ip = match (fields[1], fields[2].parse::<IpAddr>()) {
+        (_, Err(_)) => return Err(invalid_proxy_header("Invalid PROXY Protocol Source Address")),
+        ("TCP4", Ok(ip @ IpAddr::V4(_))) | ("TCP6", Ok(ip @ IpAddr::V6(_))) => ip,
+        _ => return Err(invalid_proxy_header("Mismatched PROXY Protocol Address Family")),
+    };
+    match fields[4].parse::<u16>() {
+        Ok(port) => Ok(Some(SocketAddr::new(ip, port))),
+        Err(_) => Err(invalid_proxy_header("Invalid PROXY Protocol Source Port")),
+    }
+}
+
+async fn read_v2_header<R: AsyncRead + Unpin>(stream: &mut R) -> Result<Option<SocketAddr>, io::Error>{
+    let mut header = [0; 10];
+    stream.read_exact(&mut header).await?;
+    if header[..6] != V2_SIGNATURE[6..] {
+        return Err(invalid_proxy_header("Invalid PROXY Protocol Signature"));
+    }
+    let (version_command, family) = (header[6], header[7]);
+    let length = u16::from_be_bytes([header[8], header[9]]) as usize;
+    let mut addresses = vec![0; length];
+    stream.read_exact(&mut addresses).await?;
+
+    if version_command >> 4 != 2 {
+        return Err(invalid_proxy_header("Unsupported PROXY Protocol Version"));
+    }
+    match version_command & 0x0f {
+        // LOCAL, the connection is opened by the load balancer itself
+        0 => return Ok(None),
+        1 => (),
+        _ => return Err(invalid_proxy_header("Unsupported PROXY Protocol Command")),
+    }
+    // only the source address and port are used, anything after them(e.g. TLVs) is skipped
+    match family {
+        // TCP over IPv4
+        0x11 if length >= 12 => {
+            let ip = Ipv4Addr::from(<[u8; 4]>::try_from(&addresses[0..4]).unwrap());
+            let port = u16::from_be_bytes([addresses[8], addresses[9]]);
+            Ok(Some(SocketAddr::new(IpAddr::V4(ip), port)))
+        },
+        // TCP over IPv6
+        0x21 if length >= 36 => {
+            let ip = Ipv6Addr::from(<[u8; 16]>::try_from(&addresses[0..16]).unwrap());
+            let port = u16::from_be_bytes([addresses[32], addresses[33]]);
+            Ok(Some(SocketAddr::new(IpAddr::V6(ip), port)))
+        },
+        0x11 | 0x21 => Err(invalid_proxy_header("Truncated PROXY Protocol Addresses")),
+        // unspecified, or a family other than TCP
+        _ => Ok(None),
+    }
+}
+
+fn invalid_proxy_header(reason: &str) -> io::Error{
+    io::Error::new(io::ErrorKind::InvalidData, reason.to_string())
+}
+
+#[tokio::test]
+async fn test_read_proxy_header_v1(){
+    let mut stream = io::Cursor::new(b"PROXY TCP4 203.0.113.7 10.0.0.1 56324 443\r\nGET / HTTP/1.1\r\n".to_vec());
+    assert_eq!(Some("203.0.113.7:56324".parse().unwrap()), read_proxy_header(&mut stream).await.unwrap());
+    let mut rest = String::new();
+    stream.read_to_string(&mut rest).await.unwrap();
+    assert_eq!("GET / HTTP/1.1\r\n", rest);
+
+    let mut stream = io::Cursor::new(b"PROXY TCP6 2001:db8::7 2001:db8::1 56324 443\r\n".to_vec());
+    assert_eq!(Some("[2001:db8::7]:56324".parse().unwrap()), read_proxy_header(&mut stream).await.unwrap());
+    let mut stream = io::Cursor::new(b"PROXY UNKNOWN\r\n".to_vec());
+    assert_eq!(None, read_proxy_header(&mut stream).await.unwrap());
+
+    let mut stream = io::Cursor::new(b"PROXY TCP4 2001:db8::7 10.0.0.1 56324 443\r\n".to_vec());
+    assert!(read_proxy_header(&mut stream).await.is_err());
+    let mut stream = io::Cursor::new(b"GET / HTTP/1.1\r\n\r\n".to_vec());
+    assert!(read_proxy_header(&mut stream).await.is_err());
+    let mut stream = io::Cursor::new([b"PROXY ".to_vec(), vec![b'x'; 200]].concat());
+    assert!(read_proxy_header(&mut stream).await.is_err());
+}
+
+#[tokio::test]
+async fn test_read_proxy_header_v2(){
+    let mut header = V2_SIGNATURE.to_vec();
+    // PROXY command over TCP/IPv4, followed by a TLV to be skipped
+    header.extend_from_slice(&[0x21, 0x11, 0, 16]);
+    header.extend_from_slice(&[203, 0, 113, 7, 10, 0, 0, 1, 0xdc, 0x04, 0x01, 0xbb]);
+    header.extend_from_slice(&[0x04, 0, 1, 0]);
+    header.extend_from_slice(b"GET");
+    let mut stream = io::Cursor::new(header);
+    assert_eq!(Some("203.0.113.7:56324".parse().unwrap()), read_proxy_header(&mut stream).await.unwrap());
+    let mut rest = String::new();
+    stream.read_to_string(&mut rest).await.unwrap();
+    assert_eq!("GET", rest);
+
+    // LOCAL command
+    let mut header = V2_SIGNATURE.to_vec();
+    header.extend_from_slice(&[0x20, 0x00, 0, 0]);
+    assert_eq!(None, read_proxy_header(&mut io::Cursor::new(header)).await.unwrap());
+
+    let mut header = V2_SIGNATURE.to_vec();
+    header.extend_from_slice(&[0x21, 0x21, 0, 4, 0, 0, 0, 0]);
+    assert!(read_proxy_header(&mut io::Cursor::new(header)).await.is_err());
+}
@@ -0,0 +1,167 @@
+use std::{collections::{BTreeMap, HashMap}, hash::Hash, net::{IpAddr, Ipv6Addr}, sync::Mutex, time::{Duration, Instant}};
+
+use lazy_static::lazy_static;
+
+use crate::config::{configs::CONFIGS, rate_limit::RateLimitConfig};
+
+/// The most clients tracked at a time, the least recently seen ones are forgotten beyond it
+const MAX_TRACKED_CLIENTS: usize = 10000;
+
+/// Tokens are taken one per request and refilled at a steady rate, up to the size of the bucket
+#[derive(Debug, Clone)]
+pub struct TokenBucket {
+    capacity: f64,
+    refill_rate: f64,
+    tokens: f64,
+    updated: Instant,
+}
+
+impl TokenBucket {
+    /// A full bucket
+    pub fn new(limit: &RateLimitConfig, now: Instant) -> Self{
+        let capacity = limit.get_burst() as f64;
+        TokenBucket { capacity, refill_rate: limit.get_refill_rate(), tokens: capacity, updated: now }
+    }
+
+    fn refill(&mut self, now: Instant){
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.refill_rate).min(self.capacity);
+        self.updated = now;
+    }
+
+    /// Take a token, or tell how long to wait until one is available
+    pub fn try_take(&mut self, now: Instant) -> Result<(), Duration>{
+        self.refill(now);
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            return Ok(());
+        }
+        Err(Duration::from_secs_f64((1.0 - self.tokens) / self.refill_rate))
+    }
+
+}
+
+/// The buckets of a limiter, with the order in which they are used
+struct Buckets<K> {
+    /// the bucket of each key, and the position of the key in `recent`
+    buckets: HashMap<K, (TokenBucket, u64)>,
+    /// the keys from the least recently used one
+    recent: BTreeMap<u64, K>,
+    /// the position given to the next key used
+    next: u64,
+}
+
+/// A bucket for each key, e.g. a client address, all of the same size.
+/// No more than `max_keys` buckets are kept, the least recently used one makes room for a new key.
+pub struct RateLimiter<K> {
+    limit: RateLimitConfig,
+    buckets: Mutex<Buckets<K>>,
+    max_keys: usize,
+}
+
+impl<K: Hash + Eq + Clone> RateLimiter<K> {
+    pub fn new(limit: RateLimitConfig, max_keys: usize) -> Self{
+        let buckets = Buckets { buckets: HashMap::new(), recent: BTreeMap::new(), next: 0 };
+        RateLimiter { limit, buckets: Mutex::new(buckets), max_keys }
+    }
+
+    pub fn check(&self, key: &K, now: Instant) -> Result<(), Duration>{
+        let mut guard = self.buckets.lock().unwrap();
+        let Buckets { buckets, recent, next } = &mut *guard;
+        let position = *next;
+        *next += 1;
+        match buckets.get_mut(key) {
+            Some((_, used)) => {
+                recent.remove(used);
+                *used = position;
+            },
+            None => {
+                if buckets.len() >= self.max_keys.max(1) {
+                    if let Some((_, oldest)) = recent.pop_first() {
+                        buckets.remove(&oldest);
+                    }
+                }
+                buckets.insert(key.clone(), (TokenBucket::new(&self.limit, now), position));
+            },
+        }
+        recent.insert(position, key.clone());
+        buckets.get_mut(key).unwrap().0.try_take(now)
+    }
+}
+
+/// The key of a client to be limited by, an IPv6 client is limited by its /64 network,
+/// since a single host is usually given a whole one to pick addresses from
+fn client_key(ip: IpAddr) -> IpAddr{
+    match ip.to_canonical() {
+        IpAddr::V6(v6) => IpAddr::V6(Ipv6Addr::from(v6.to_bits() & !((1u128 << 64) - 1))),
+        v4 => v4,
+    }
+}
+
+lazy_static! {
+    static ref HOOK_LIMITERS: HashMap<String, Mutex<TokenBucket>> = CONFIGS.hooks
+        .iter()
+        .filter_map(|h| h.rate_limit.as_ref().map(|l| (h.id.clone(), Mutex::new(TokenBucket::new(l, Instant::now())))))
+        .collect();
+    static ref CLIENT_LIMITER: Option<RateLimiter<IpAddr>> = CONFIGS.global.client_rate_limit
+        .map(|l| RateLimiter::new(l, MAX_TRACKED_CLIENTS));
+}
+
+/// Count a request against the global `client_rate_limit` of the client,
+/// a request whose client address is unknown is not limited
+pub fn check_client_rate_limit(client_ip: Option<IpAddr>) -> Result<(), Duration>{
+    match (CLIENT_LIMITER.as_ref(), client_ip) {
+        (Some(limiter), Some(ip)) => limiter.check(&client_key(ip), Instant::now()),
+        _ => Ok(()),
+    }
+}
+
+/// Count a delivery against the `rate_limit` of the hook
+pub fn check_hook_rate_limit(hook_id: &str) -> Result<(), Duration>{
+    match HOOK_LIMITERS.get(hook_id) {
+        Some(bucket) => bucket.lock().unwrap().try_take(Instant::now()),
+        None => Ok(()),
+    }
+}
+
+#[test]
+fn test_token_bucket(){
+    let now = Instant::now();
+    let mut limit = RateLimitConfig::new(6, 60);
+    limit.burst = Some(2);
+    let mut bucket = TokenBucket::new(&limit, now);
+    assert!(bucket.try_take(now).is_ok());
+    assert!(bucket.try_take(now).is_ok());
+    assert_eq!(10.0, bucket.try_take(now).unwrap_err().as_secs_f64().round());
+    assert_eq!(4.0, bucket.try_take(now + Duration::from_secs(6)).unwrap_err().as_secs_f64().round());
+    assert!(bucket.try_take(now + Duration::from_secs(10)).is_ok());
+    // no more than the burst is saved up
+    let later = now + Duration::from_secs(3600);
+    assert!(bucket.try_take(later).is_ok());
+    assert!(bucket.try_take(later).is_ok());
+    assert!(bucket.try_take(later).is_err());
+}
+
+#[test]
+fn test_rate_limiter(){
+    let now = Instant::now();
+    let limiter = RateLimiter::new(RateLimitConfig::new(1, 60), 2);
+    assert!(limiter.check(&"a", now).is_ok());
+    assert!(limiter.check(&"a", now).is_err());
+    assert!(limiter.check(&"b", now).is_ok());
+    // the least recently seen client `a` is forgotten to make room, however empty its bucket is
+    assert!(limiter.check(&"b", now).is_err());
+    assert!(limiter.check(&"c", now).is_ok());
+    assert!(limiter.check(&"b", now).is_err());
+    let buckets = limiter.buckets.lock().unwrap();
+    assert_eq!(2, buckets.buckets.len());
+    assert_eq!(2, buckets.recent.len());
+    assert!(!buckets.buckets.contains_key(&"a"));
+}
+
+#[test]
+fn test_client_key(){
+    assert_eq!("2001:db8:1:2::".parse::<IpAddr>().unwrap(), client_key("2001:db8:1:2:aaaa:bbbb:cccc:dddd".parse().unwrap()));
+    assert_eq!("192.0.2.7".parse::<IpAddr>().unwrap(), client_key("::ffff:192.0.2.7".parse().unwrap()));
+    assert_eq!("192.0.2.7".parse::<IpAddr>().unwrap(), client_key("192.0.2.7".parse().unwrap()));
+}
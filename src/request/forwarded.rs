@@ -0,0 +1,185 @@
+use std::net::IpAddr;
+
+use ipnet::IpNet;
+use lazy_static::lazy_static;
+
+use crate::{config::configs::CONFIGS, stream::PeerAddress};
+
+use super::Headers;
+
+lazy_static! {
+    /// parsed once, the entries are checked when the config is loaded
+    pub static ref TRUSTED_PROXIES: TrustedProxies = CONFIGS.global.get_trusted_proxies().unwrap_or_default();
+}
+
+/// The single header the trusted proxies set, from the global `forwarded_header`.
+/// The others are ignored, as a proxy passes on whatever the client sent in them.
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub enum ForwardedHeader {
+    Forwarded,
+    #[default]
+    XForwardedFor,
+    XRealIp,
+}
+
+impl ForwardedHeader {
+    pub fn new(name: &str) -> Result<Self, String>{
+        match name.trim().to_lowercase().as_str() {
+            "forwarded" => Ok(ForwardedHeader::Forwarded),
+            "x-forwarded-for" => Ok(ForwardedHeader::XForwardedFor),
+            "x-real-ip" => Ok(ForwardedHeader::XRealIp),
+            _ => Err(format!("Invalid forwarded header [{}], expected forwarded, x-forwarded-for or x-real-ip", name)),
+        }
+    }
+}
+
+/// Proxies whose forwarding header is believed, parsed from the global `trusted_proxies`
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct TrustedProxies {
+    networks: Vec<IpNet>,
+    /// whether a peer on a unix domain socket is trusted
+    unix: bool,
+    header: ForwardedHeader,
+}
+
+impl TrustedProxies {
+    /// Each entry is a CIDR, a single address, or `unix` for any peer on a unix domain socket
+    pub fn new(entries: &[String], header: ForwardedHeader) -> Result<Self, String>{
+        let mut proxies = TrustedProxies { header, ..Default::default() };
+        for entry in entries.iter().map(|e| e.trim()){
+            if entry == "unix" {
+                proxies.unix = true;
+            } else if let Ok(net) = entry.parse::<IpNet>() {
+                proxies.networks.push(net);
+            } else if let Ok(ip) = entry.parse::<IpAddr>() {
+                proxies.networks.push(IpNet::from(ip));
+            } else {
+                return Err(format!("Invalid trusted proxy [{}]", entry));
+            }
+        }
+        Ok(proxies)
+    }
+
+    fn contains(&self, ip: &IpAddr) -> bool{
+        self.networks.iter().any(|n| n.contains(&ip.to_canonical()))
+    }
+
+    /// The address of the client on whose behalf the request is sent.
+    /// Forwarding headers are only followed from a trusted peer, walking the chain of proxies
+    /// from the nearest one until an address that is not trusted.
+    /// `None` if the address cannot be told, e.g. a hop is `unknown` or obfuscated.
+    /// An IPv4-mapped IPv6 address is turned into the IPv4 one.
+    pub fn resolve_client_ip(&self, peer: &PeerAddress, headers: &Headers) -> Option<IpAddr>{
+        self.resolve(peer, headers).map(|ip| ip.to_canonical())
+    }
+
+    fn resolve(&self, peer: &PeerAddress, headers: &Headers) -> Option<IpAddr>{
+        let peer_ip = match peer {
+            PeerAddress::Tcp(addr) if self.contains(&addr.ip()) => addr.ip(),
+            PeerAddress::Tcp(addr) => return Some(addr.ip()),
+            PeerAddress::Unix(_) if self.unix => return self.resolve_forwarded(headers, None),
+            PeerAddress::Unix(_) => return None,
+        };
+        self.resolve_forwarded(headers, Some(peer_ip))
+    }
+
+    fn resolve_forwarded(&self, headers: &Headers, peer_ip: Option<IpAddr>) -> Option<IpAddr>{
+        let chain: Vec<String> = match self.header {
+            ForwardedHeader::Forwarded => headers.get_all("Forwarded").iter().flat_map(|v| parse_forwarded_for(v)).collect(),
+            ForwardedHeader::XForwardedFor => headers.get_all("X-Forwarded-For").iter().flat_map(|v| v.split(',')).map(|v| v.trim().to_string()).collect(),
+            ForwardedHeader::XRealIp => headers.get_all("X-Real-IP").last().map(|v| vec![v.trim().to_string()]).unwrap_or_default(),
+        };
+
+        let mut client_ip = peer_ip;
+        for hop in chain.iter().rev(){
+            let ip = match parse_node(hop) {
+                Some(ip) => ip,
+                None => {
+                    log::warn!("Cannot tell client address from forwarded hop [{}]", hop);
+                    return None;
+                }
+            };
+            client_ip = Some(ip);
+            if !self.contains(&ip){
+                break;
+            }
+        }
+        client_ip
+    }
+}
+
+/// The `for` parameters of a `Forwarded` header value(RFC 7239), one per hop
+fn parse_forwarded_for(value: &str) -> Vec<String>{
+    value
+        .split(',')
+        .filter_map(|element| {
+            element
+                .split(';')
+                .filter_map(|pair| pair.split_once('='))
+                .find(|(k, _)| k.trim().eq_ignore_ascii_case("for"))
+                .map(|(_, v)| v.trim().trim_matches('"').to_string())
+        })
+        .collect()
+}
+
+/// The address of a hop, which may come with a port, e.g. `192.0.2.60:4711` or `[2001:db8::17]:4711`
+fn parse_node(node: &str) -> Option<IpAddr>{
+    if let Ok(ip) = node.parse::<IpAddr>() {
+        return Some(ip);
+    }
+    // the zone ID of an IPv6 address is dropped, e.g. `[fe80::1%eth0]:4711`
+    if let Some(rest) = node.strip_prefix('[') {
+        let addr = rest.split(']').next()?;
+        return addr.split('%').next()?.parse().ok();
+    }
+    if let Some((addr, _)) = node.split_once('%') {
+        return addr.parse().ok();
+    }
+    node.rsplit_once(':')?.0.parse::<std::net::Ipv4Addr>().ok().map(IpAddr::V4)
+}
+
+#[test]
+fn test_resolve_client_ip(){
+    assert!(TrustedProxies::new(&["10.0.0.0/8".to_string(), "bad".to_string()], ForwardedHeader::default()).is_err());
+    assert!(ForwardedHeader::new("X-Client-IP").is_err());
+    let entries = ["10.0.0.0/8".to_string(), "::1".to_string()];
+    let proxies = TrustedProxies::new(&entries, ForwardedHeader::new("X-Forwarded-For").unwrap()).unwrap();
+    let peer = PeerAddress::Tcp("10.0.0.2:40000".parse().unwrap());
+    let mut headers = Headers::new();
+    assert_eq!(Some("10.0.0.2".parse().unwrap()), proxies.resolve_client_ip(&peer, &headers));
+
+    // the client may prepend anything, only the hops appended by trusted proxies count
+    headers.append("X-Forwarded-For", "1.1.1.1, 203.0.113.7");
+    headers.append("X-Forwarded-For", "10.0.0.9");
+    assert_eq!(Some("203.0.113.7".parse().unwrap()), proxies.resolve_client_ip(&peer, &headers));
+
+    // the headers of an untrusted peer are ignored
+    let untrusted = PeerAddress::Tcp("198.51.100.1:40000".parse().unwrap());
+    assert_eq!(Some("198.51.100.1".parse().unwrap()), proxies.resolve_client_ip(&untrusted, &headers));
+
+    // a `Forwarded` header the proxy does not set is passed on from the client, and ignored
+    headers.append("Forwarded", "for=192.0.2.1");
+    headers.append("X-Real-IP", "192.0.2.2");
+    assert_eq!(Some("203.0.113.7".parse().unwrap()), proxies.resolve_client_ip(&peer, &headers));
+
+    let proxies = TrustedProxies::new(&entries, ForwardedHeader::Forwarded).unwrap();
+    headers.set("Forwarded", "for=\"[2001:db8::17]:4711\";proto=https, for=10.0.0.9:80");
+    assert_eq!(Some("2001:db8::17".parse().unwrap()), proxies.resolve_client_ip(&peer, &headers));
+    headers.set("Forwarded", "for=\"[fe80::1%eth0]:4711\"");
+    assert_eq!(Some("fe80::1".parse().unwrap()), proxies.resolve_client_ip(&peer, &headers));
+    headers.set("Forwarded", "for=unknown");
+    assert_eq!(None, proxies.resolve_client_ip(&peer, &headers));
+
+    let proxies = TrustedProxies::new(&entries, ForwardedHeader::XRealIp).unwrap();
+    let mut headers = Headers::new();
+    headers.append("X-Real-IP", "192.0.2.60");
+    headers.append("X-Forwarded-For", "192.0.2.1");
+    assert_eq!(Some("192.0.2.60".parse().unwrap()), proxies.resolve_client_ip(&peer, &headers));
+
+    let unix = PeerAddress::Unix("/run/rusthook.sock".to_string());
+    assert_eq!(None, proxies.resolve_client_ip(&unix, &headers));
+    let mapped = PeerAddress::Tcp("[::ffff:10.0.0.2]:40000".parse().unwrap());
+    assert_eq!(Some("192.0.2.60".parse().unwrap()), proxies.resolve_client_ip(&mapped, &headers));
+    let proxies = TrustedProxies::new(&["unix".to_string()], ForwardedHeader::XRealIp).unwrap();
+    assert_eq!(Some("192.0.2.60".parse().unwrap()), proxies.resolve_client_ip(&unix, &headers));
+}
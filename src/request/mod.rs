@@ -0,0 +1,424 @@
+use std::{io, net::IpAddr};
+
+use serde_json::Value;
+
+use crate::{stream::PeerAddress, multipart::{get_header_param, parse_multipart, Part}, parser::{encoding::decode_content, form_fields_to_json, parse_form_urlencoded, parse_hook_id_from_url, parse_parameters_from_url, xml::parse_xml}, stream::Peer};
+
+pub mod forwarded;
+
+/// The names of the request attributes referred to by the `request` source, matched case-insensitively
+pub const REQUEST_ATTRIBUTES: [&str; 7] = [
+    "method", "peer-address", "client-ip", "url", "version", "client-cert-subject", "client-cert-fingerprint",
+];
+
+/// Header fields of a request in the order they are received.
+/// Names are matched case-insensitively, and every value of a repeated field is kept.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct Headers {
+    fields: Vec<(String, String)>,
+}
+
+impl Headers {
+    pub fn new() -> Self{
+        Headers { fields: Vec::new() }
+    }
+
+    /// Add a value to the field, keeping the values already received
+    pub fn append(&mut self, name: &str, value: &str){
+        self.fields.push((name.to_string(), value.to_string()));
+    }
+
+    /// Replace all the values of the field
+    pub fn set(&mut self, name: &str, value: &str){
+        self.remove(name);
+        self.append(name, value);
+    }
+
+    pub fn remove(&mut self, name: &str){
+        self.fields.retain(|(n, _)| !n.eq_ignore_ascii_case(name));
+    }
+
+    pub fn contains(&self, name: &str) -> bool{
+        self.fields.iter().any(|(n, _)| n.eq_ignore_ascii_case(name))
+    }
+
+    /// All the values of the field, in the order they are received
+    pub fn get_all(&self, name: &str) -> Vec<&str>{
+        self.fields
+            .iter()
+            .filter(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+            .collect()
+    }
+
+    /// The values of a repeated field are combined into one, separated by `, `
+    pub fn get(&self, name: &str) -> Option<String>{
+        let values = self.get_all(name);
+        if values.is_empty(){
+            return None;
+        }
+        Some(values.join(", "))
+    }
+
+    /// Pick the values of a repeated field by `select`, which is one of
+    /// `first`, `last`, `all`(combined like `get`), or a zero-based index
+    pub fn select(&self, name: &str, select: &str) -> Option<String>{
+        select_value(&self.get_all(name), select, name)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)>{
+        self.fields.iter().map(|(n, v)| (n.as_str(), v.as_str()))
+    }
+
+    /// Add a field line received in a header or trailer section, `name: value`.
+    /// The name is split at the first `:`, and must be a token without any whitespace,
+    /// as a malformed one could be read differently by a proxy in front, e.g. `Content-Length :5`.
+    /// The whitespace around the value is dropped.
+    pub fn append_line(&mut self, line: &str) -> Result<(), io::Error>{
+        let (name, value) = match line.split_once(':') {
+            Some((name, value)) if is_token(name) => (name, value.trim_matches([' ', '\t'])),
+            _ => {
+                log::warn!("Invalid header field line [{}]", line);
+                return Err(io::Error::new(io::ErrorKind::InvalidData, "Invalid Header Field"));
+            }
+        };
+        if value.contains(['\r', '\n', '\0']) {
+            log::warn!("Invalid value of header field [{}]", name);
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "Invalid Header Field"));
+        }
+        self.append(name, value);
+        Ok(())
+    }
+}
+
+/// A token(RFC 9110 §5.6.2), e.g. a field name or a method
+fn is_token(s: &str) -> bool{
+    !s.is_empty() && s.bytes().all(|b| b.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&b))
+}
+
+/// Pick from the values of a repeated field by `select`, which is one of
+/// `first`, `last`, `all`(joined by `, `), or a zero-based index
+fn select_value(values: &[&str], select: &str, name: &str) -> Option<String>{
+    if values.is_empty(){
+        return None;
+    }
+    let value = match select {
+        "all" => return Some(values.join(", ")),
+        "first" => values.first(),
+        "last" => values.last(),
+        s => match s.parse::<usize>() {
+            Ok(i) => values.get(i),
+            Err(e) => {
+                log::error!("Invalid selection [{}] of [{}]: {}", s, name, e);
+                None
+            }
+        },
+    };
+    value.map(|v| v.to_string())
+}
+
+/// A request received from a peer
+#[derive(Debug, PartialEq, Clone)]
+pub struct HttpRequest {
+    pub method: String,
+    /// the path of the request target, without the query
+    pub path: String,
+    /// the query of the request target, without the leading `?`
+    pub query: Option<String>,
+    pub version: String,
+    pub headers: Headers,
+    /// trailer fields sent after a chunked body, kept apart from `headers` since they arrive
+    /// after the request has been framed and routed
+    pub trailers: Headers,
+    /// raw bytes of the body as received, `None` if the request is sent without one
+    pub body: Option<Vec<u8>>,
+    /// the body with its `Content-Encoding` undone, `None` if it is sent without one
+    pub decoded_body: Option<Vec<u8>>,
+    pub peer: Peer,
+    /// the address of the client, which differs from the peer's behind a trusted proxy,
+    /// `None` if it cannot be told
+    pub client_ip: Option<IpAddr>,
+}
+
+impl HttpRequest {
+    /// A request with no header fields and no body, `target` is split into the path and the query
+    pub fn new(method: &str, target: &str, version: &str, peer: Peer) -> Self{
+        let (path, query) = match target.split_once('?') {
+            Some((p, q)) => (p.to_string(), Some(q.to_string())),
+            None => (target.to_string(), None),
+        };
+        HttpRequest {
+            method: method.to_string(),
+            path,
+            query,
+            version: version.to_string(),
+            headers: Headers::new(),
+            trailers: Headers::new(),
+            body: None,
+            decoded_body: None,
+            client_ip: match &peer.address {
+                PeerAddress::Tcp(addr) => Some(addr.ip().to_canonical()),
+                PeerAddress::Unix(_) => None,
+            },
+            peer,
+        }
+    }
+
+    /// The request target as sent by the peer
+    pub fn url(&self) -> String{
+        match &self.query {
+            Some(q) => format!("{}?{}", self.path, q),
+            None => self.path.clone(),
+        }
+    }
+
+    /// The value of a request attribute in `REQUEST_ATTRIBUTES`, never taken from a header of the same name
+    pub fn attribute(&self, name: &str) -> Option<String>{
+        let client_cert = self.peer.client_cert.as_ref();
+        match name.to_lowercase().as_str(){
+            "method" => Some(self.method.clone()),
+            "peer-address" => Some(self.peer.address.to_string()),
+            "client-ip" => self.client_ip.map(|ip| ip.to_string()),
+            "url" => Some(self.url()),
+            "version" => Some(self.version.clone()),
+            "client-cert-subject" => client_cert.map(|c| c.subject.clone()),
+            "client-cert-fingerprint" => client_cert.map(|c| c.fingerprint.clone()),
+            _ => None,
+        }
+    }
+
+    /// The id of the requested hook, which is the decoded path without the surrounding slashes
+    pub fn hook_id(&self) -> String{
+        parse_hook_id_from_url(&self.path)
+    }
+
+    /// The decoded query parameters in the order they are sent, a repeated key is kept once per value
+    pub fn query_parameters(&self) -> Vec<(String, String)>{
+        match &self.query {
+            Some(q) => parse_parameters_from_url(&format!("?{}", q)),
+            None => Vec::new(),
+        }
+    }
+
+    /// Pick from the values of a query parameter like `Headers::select`, names are case-sensitive
+    pub fn query_select(&self, name: &str, select: &str) -> Option<String>{
+        let params = self.query_parameters();
+        let values: Vec<&str> = params
+            .iter()
+            .filter(|(k, _)| k == name)
+            .map(|(_, v)| v.as_str())
+            .collect();
+        select_value(&values, select, name)
+    }
+
+    /// Undo the `Content-Encoding` of the body, if any, keeping the body as received.
+    /// The decoded body is limited to `max_body_size` like the received one.
+    pub fn decode_body(&mut self, max_body_size: u64) -> Result<(), io::Error>{
+        let content_encoding = match self.headers.get("Content-Encoding") {
+            Some(e) => e,
+            None => return Ok(()),
+        };
+        if let Some(body) = &self.body {
+            self.decoded_body = Some(decode_content(body, &content_encoding, max_body_size)?);
+        }
+        Ok(())
+    }
+
+    /// The bytes of the body payload values are taken from, which is the decoded body if it is encoded
+    pub fn content(&self) -> Option<&[u8]>{
+        self.decoded_body.as_deref().or(self.body.as_deref())
+    }
+
+    /// The body decoded as UTF-8, `None` if there is no body or it is not valid UTF-8
+    pub fn body_str(&self) -> Option<&str>{
+        match self.content() {
+            Some(b) => match std::str::from_utf8(b) {
+                Ok(s) => Some(s),
+                Err(e) => {
+                    log::warn!("Request body is not valid UTF-8: {}", e);
+                    None
+                }
+            },
+            None => None,
+        }
+    }
+
+    /// The body parsed as JSON from its bytes, `None` if there is no body or it is not valid JSON
+    pub fn body_json(&self) -> Option<Value>{
+        match serde_json::from_slice(self.content()?) {
+            Ok(v) => Some(v),
+            Err(e) => {
+                log::warn!("Request body is not valid JSON: {}", e);
+                None
+            }
+        }
+    }
+
+    /// The media type of the body in lower case without parameters, e.g. `application/json`
+    pub fn content_type(&self) -> Option<String>{
+        let content_type = self.headers.get_all("Content-Type").first()?.to_string();
+        let media_type = content_type.split(';').next().unwrap_or_default();
+        Some(media_type.trim().to_lowercase())
+    }
+
+    /// The body decoded according to its `Content-Type` into a value the dotted names can address,
+    /// only the text fields of a multipart form are included, and a body of any other type is parsed as JSON
+    pub fn payload(&self) -> Option<Value>{
+        match self.content_type().as_deref() {
+            Some(t) if t == "application/xml" || t == "text/xml" || t.ends_with("+xml") => parse_xml(self.content()?).ok(),
+            Some("application/x-www-form-urlencoded") => Some(parse_form_urlencoded(self.content()?)),
+            Some("multipart/form-data") => {
+                let fields = self.multipart()?
+                    .into_iter()
+                    .filter(|p| p.filename.is_none())
+                    .map(|p| (p.name, String::from_utf8_lossy(&p.data).to_string()));
+                Some(form_fields_to_json(fields))
+            },
+            _ => self.body_json(),
+        }
+    }
+
+    /// The parts of a `multipart/form-data` body, `None` if the body is not a valid one
+    pub fn multipart(&self) -> Option<Vec<Part>>{
+        if self.content_type().as_deref() != Some("multipart/form-data"){
+            return None;
+        }
+        let content_type = self.headers.get_all("Content-Type").first()?.to_string();
+        let boundary = match get_header_param(&content_type, "boundary") {
+            Some(b) => b,
+            None => {
+                log::warn!("No boundary in Content-Type [{}]", content_type);
+                return None;
+            }
+        };
+        parse_multipart(self.content()?, &boundary).ok()
+    }
+
+    /// Decide whether the connection should be kept open after responding to the request.
+    /// HTTP/1.1 connections are persistent unless the client sends `Connection: close`,
+    /// while HTTP/1.0 connections are closed unless the client sends `Connection: keep-alive`.
+    pub fn is_keep_alive(&self) -> bool{
+        let tokens: Vec<String> = self.headers
+            .get_all("Connection")
+            .iter()
+            .flat_map(|v| v.split(','))
+            .map(|t| t.trim().to_lowercase())
+            .collect();
+        match self.version.as_str(){
+            "HTTP/1.1" => !tokens.iter().any(|t| t == "close"),
+            _ => tokens.iter().any(|t| t == "keep-alive"),
+        }
+    }
+}
+
+#[cfg(test)]
+pub fn test_peer() -> Peer{
+    Peer {
+        address: crate::stream::PeerAddress::Tcp("127.0.0.1:56020".parse().unwrap()),
+        client_cert: None,
+        listener: String::from("default"),
+    }
+}
+
+#[test]
+fn test_headers(){
+    let mut headers = Headers::new();
+    headers.append("Accept", "text/plain");
+    headers.append("accept", "application/json");
+    assert_eq!(Some("text/plain, application/json".to_string()), headers.get("ACCEPT"));
+    assert_eq!(vec!["text/plain", "application/json"], headers.get_all("Accept"));
+    assert_eq!(Some("text/plain".to_string()), headers.select("accept", "first"));
+    assert_eq!(Some("application/json".to_string()), headers.select("accept", "last"));
+    assert_eq!(Some("application/json".to_string()), headers.select("accept", "1"));
+    assert_eq!(Some("text/plain, application/json".to_string()), headers.select("accept", "all"));
+    assert_eq!(None, headers.select("accept", "2"));
+    assert_eq!(None, headers.select("accept", "second"));
+    headers.set("Accept", "*/*");
+    assert_eq!(vec!["*/*"], headers.get_all("accept"));
+    headers.remove("Accept");
+    assert!(!headers.contains("Accept"));
+}
+
+#[test]
+fn test_http_request_target(){
+    let request = HttpRequest::new("GET", "/hook/?x=1&y=2", "HTTP/1.1", test_peer());
+    assert_eq!("hook", request.hook_id());
+    assert_eq!("/hook/?x=1&y=2", request.url());
+    assert_eq!(Some("2".to_string()), request.query_select("y", "all"));
+
+    let request = HttpRequest::new("GET", "/my%20hook?tag=a&tag=b+c&flag", "HTTP/1.1", test_peer());
+    assert_eq!("my hook", request.hook_id());
+    assert_eq!(Some("a".to_string()), request.query_select("tag", "first"));
+    assert_eq!(Some("b c".to_string()), request.query_select("tag", "1"));
+    assert_eq!(Some("a, b c".to_string()), request.query_select("tag", "all"));
+    assert_eq!(Some("".to_string()), request.query_select("flag", "all"));
+    assert_eq!(None, request.query_select("Tag", "all"));
+
+    let request = HttpRequest::new("GET", "/hook", "HTTP/1.1", test_peer());
+    assert_eq!(None, request.query);
+    assert!(request.query_parameters().is_empty());
+}
+
+#[test]
+fn test_is_keep_alive(){
+    let mut request = HttpRequest::new("GET", "/", "HTTP/1.1", test_peer());
+    assert!(request.is_keep_alive());
+    request.headers.append("Connection", "close");
+    assert!(!request.is_keep_alive());
+
+    let mut request = HttpRequest::new("GET", "/", "HTTP/1.0", test_peer());
+    assert!(!request.is_keep_alive());
+    request.headers.append("connection", "Keep-Alive");
+    assert!(request.is_keep_alive());
+}
+
+#[test]
+fn test_binary_body(){
+    let mut request = HttpRequest::new("POST", "/", "HTTP/1.1", test_peer());
+    request.body = Some(vec![0x08, 0x96, 0x01, 0xff]);
+    assert_eq!(None, request.body_str());
+    assert_eq!(None, request.body_json());
+    request.body = Some(b"{\"a\":\"b\"}".to_vec());
+    assert_eq!(Some("b"), request.body_json().unwrap()["a"].as_str());
+}
+
+#[test]
+fn test_form_payload(){
+    let mut request = HttpRequest::new("POST", "/", "HTTP/1.1", test_peer());
+    request.headers.append("Content-Type", "Application/X-WWW-Form-Urlencoded; charset=utf-8");
+    request.body = Some(b"command=%2Fdeploy&text=web+prod".to_vec());
+    assert_eq!(Some("application/x-www-form-urlencoded".to_string()), request.content_type());
+    let payload = request.payload().unwrap();
+    assert_eq!(Some("/deploy"), payload["command"].as_str());
+    assert_eq!(Some("web prod"), payload["text"].as_str());
+}
+
+#[test]
+fn test_xml_payload(){
+    let mut request = HttpRequest::new("POST", "/", "HTTP/1.1", test_peer());
+    request.headers.append("Content-Type", "application/soap+xml; charset=utf-8");
+    request.body = Some(b"<alert host=\"db-1\"><state>DOWN</state></alert>".to_vec());
+    let payload = request.payload().unwrap();
+    assert_eq!(Some("db-1"), payload["alert"]["@host"].as_str());
+    assert_eq!(Some("DOWN"), payload["alert"]["state"].as_str());
+}
+
+#[test]
+fn test_decode_body(){
+    use std::io::Write;
+    use flate2::{write::GzEncoder, Compression};
+
+    let mut request = HttpRequest::new("POST", "/", "HTTP/1.1", test_peer());
+    request.body = Some(b"{\"a\":\"b\"}".to_vec());
+    request.decode_body(1024).unwrap();
+    assert_eq!(None, request.decoded_body);
+
+    let mut gzip = GzEncoder::new(Vec::new(), Compression::default());
+    gzip.write_all(b"{\"a\":\"b\"}").unwrap();
+    let gzip = gzip.finish().unwrap();
+    request.headers.append("Content-Encoding", "gzip");
+    request.body = Some(gzip.clone());
+    request.decode_body(1024).unwrap();
+    assert_eq!(Some(gzip), request.body);
+    assert_eq!(Some("b"), request.payload().unwrap()["a"].as_str());
+}
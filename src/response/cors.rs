@@ -0,0 +1,145 @@
+use crate::{config::Config, request::HttpRequest};
+
+/// The CORS headers of a response from a hook to a browser, empty if the hook has no `cors`.
+/// Only `Vary: Origin` is sent if the request is not cross-origin or its origin is not allowed,
+/// since the response differs by origin unless any origin is allowed.
+/// A preflight is answered with what it asks for only if the method and headers are allowed,
+/// otherwise with no CORS headers at all, so that the browser does not send the actual request.
+pub fn cors_headers(config: &Config, http_request: &HttpRequest) -> Vec<(String, String)>{
+    let cors = match &config.cors {
+        Some(cors) => cors,
+        None => return Vec::new(),
+    };
+    // a wildcard never comes with credentials, which `CorsConfig::validate` rejects
+    let any_origin = cors.allowed_origins.iter().any(|o| o == "*");
+    let vary = match any_origin {
+        true => Vec::new(),
+        false => vec![("Vary".to_string(), "Origin".to_string())],
+    };
+    let origin = match http_request.headers.get("Origin") {
+        Some(origin) if cors.allows_origin(&origin) => origin,
+        _ => return vary,
+    };
+
+    let mut headers = vary.clone();
+    match any_origin {
+        true => headers.push(("Access-Control-Allow-Origin".to_string(), "*".to_string())),
+        false => headers.push(("Access-Control-Allow-Origin".to_string(), origin)),
+    }
+    if cors.allow_credentials {
+        headers.push(("Access-Control-Allow-Credentials".to_string(), "true".to_string()));
+    }
+
+    if !is_preflight(http_request) {
+        return headers;
+    }
+    match preflight_headers(config, http_request) {
+        Some(preflight) => headers.extend(preflight),
+        None => return vary,
+    }
+    headers
+}
+
+/// An `OPTIONS` request sent by a browser to ask whether the actual request is allowed
+pub fn is_preflight(http_request: &HttpRequest) -> bool{
+    http_request.method == "OPTIONS"
+        && http_request.headers.contains("Origin")
+        && http_request.headers.contains("Access-Control-Request-Method")
+}
+
+fn preflight_headers(config: &Config, http_request: &HttpRequest) -> Option<Vec<(String, String)>>{
+    let cors = config.cors.as_ref()?;
+    let allowed_methods = match cors.allowed_methods.is_empty() {
+        true => config.http_methods.iter().map(|m| m.trim().to_uppercase()).collect(),
+        false => cors.allowed_methods.iter().map(|m| m.trim().to_uppercase()).collect::<Vec<String>>(),
+    };
+    let method = http_request.headers.get("Access-Control-Request-Method")?;
+    if !allowed_methods.contains(&method) || !config.allows_method(&method) {
+        log::warn!("Preflight of method [{}] not allowed for hook [{}]", method, config.id);
+        return None;
+    }
+    let requested_headers = http_request.headers.get("Access-Control-Request-Headers").unwrap_or_default();
+    let requested_headers: Vec<&str> = requested_headers.split(',').map(|h| h.trim()).filter(|h| !h.is_empty()).collect();
+    if let Some(header) = requested_headers.iter().find(|h| !cors.allows_header(h)) {
+        log::warn!("Preflight of header [{}] not allowed for hook [{}]", header, config.id);
+        return None;
+    }
+
+    let mut headers = vec![
+        ("Access-Control-Allow-Methods".to_string(), allowed_methods.join(", ")),
+        ("Vary".to_string(), "Access-Control-Request-Method, Access-Control-Request-Headers".to_string()),
+    ];
+    if !requested_headers.is_empty() {
+        headers.push(("Access-Control-Allow-Headers".to_string(), requested_headers.join(", ")));
+    }
+    if let Some(max_age) = cors.max_age {
+        headers.push(("Access-Control-Max-Age".to_string(), max_age.to_string()));
+    }
+    Some(headers)
+}
+
+/// Add headers to a buffered response, right before the blank line ending its headers
+pub fn insert_response_headers(response: &mut Vec<u8>, headers: &[(String, String)]){
+    let end = match response.windows(4).position(|w| w == b"\r\n\r\n") {
+        Some(end) => end + 2,
+        None => return,
+    };
+    let headers: String = headers.iter().map(|(name, value)| format!("{}: {}\r\n", name, value)).collect();
+    response.splice(end..end, headers.into_bytes());
+}
+
+#[test]
+fn test_cors_headers(){
+    use crate::config::cors::CorsConfig;
+
+    let mut config = Config::new();
+    let mut http_request = HttpRequest::new("POST", "/redeploy", "HTTP/1.1", crate::request::test_peer());
+    http_request.headers.set("Origin", "https://dashboard.example.com");
+    assert!(cors_headers(&config, &http_request).is_empty());
+
+    let mut cors = CorsConfig::new(vec!["https://dashboard.example.com".to_string()]);
+    cors.allowed_headers = vec!["Content-Type".to_string()];
+    cors.max_age = Some(600);
+    config.cors = Some(cors);
+    let headers = cors_headers(&config, &http_request);
+    assert!(headers.contains(&("Access-Control-Allow-Origin".to_string(), "https://dashboard.example.com".to_string())));
+    assert!(headers.contains(&("Vary".to_string(), "Origin".to_string())));
+    assert!(!headers.iter().any(|(name, _)| name == "Access-Control-Allow-Methods"));
+
+    http_request.method = "OPTIONS".to_string();
+    http_request.headers.set("Access-Control-Request-Method", "POST");
+    http_request.headers.set("Access-Control-Request-Headers", "content-type");
+    let headers = cors_headers(&config, &http_request);
+    assert!(headers.contains(&("Access-Control-Allow-Methods".to_string(), "GET, POST".to_string())));
+    assert!(headers.contains(&("Access-Control-Allow-Headers".to_string(), "content-type".to_string())));
+    assert!(headers.contains(&("Access-Control-Max-Age".to_string(), "600".to_string())));
+
+    // a preflight asking for more than allowed is not answered with CORS headers
+    let vary = vec![("Vary".to_string(), "Origin".to_string())];
+    http_request.headers.set("Access-Control-Request-Headers", "content-type, x-token");
+    assert_eq!(vary, cors_headers(&config, &http_request));
+    http_request.headers.set("Access-Control-Request-Headers", "content-type");
+    http_request.headers.set("Access-Control-Request-Method", "DELETE");
+    assert_eq!(vary, cors_headers(&config, &http_request));
+
+    // the responses to other origins, or to no origin, still vary by origin for caches
+    http_request.headers.set("Origin", "https://evil.example.com");
+    http_request.headers.set("Access-Control-Request-Method", "POST");
+    assert_eq!(vary, cors_headers(&config, &http_request));
+    http_request.headers.remove("Origin");
+    assert_eq!(vary, cors_headers(&config, &http_request));
+
+    // while a response allowed to any origin does not
+    config.cors = Some(CorsConfig::new(vec!["*".to_string()]));
+    assert!(cors_headers(&config, &http_request).is_empty());
+    http_request.method = "POST".to_string();
+    http_request.headers.set("Origin", "https://evil.example.com");
+    assert_eq!(vec![("Access-Control-Allow-Origin".to_string(), "*".to_string())], cors_headers(&config, &http_request));
+}
+
+#[test]
+fn test_insert_response_headers(){
+    let mut response = b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok".to_vec();
+    insert_response_headers(&mut response, &[("Vary".to_string(), "Origin".to_string())]);
+    assert_eq!(b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\nVary: Origin\r\n\r\nok".to_vec(), response);
+}
@@ -1,8 +1,9 @@
-use std::{io::{self, Write}, process::Child, collections::HashMap, net::TcpStream};
+use std::{io::{self, Write}, process::Child, collections::HashMap};
 use serde_json::json;
 
-use crate::config::Config;
+use crate::{config::Config, request::HttpRequest};
 use favicon::FAVICON;
+pub mod cors;
 pub mod favicon;
 
 pub fn format_response_headers_to_string(headers: &[HashMap<String, String>]) -> String{
@@ -16,10 +17,21 @@ pub fn format_response_headers_to_string(headers: &[HashMap<String, String>]) ->
     .join("\r\n")
 }
 
-pub fn http_response_with_child(stream: &mut TcpStream, child: &Child, http_request: &HashMap<String, String>, config: &Config) {
-    let status_line = format!("{} 200 OK", http_request.get("Version").unwrap());
+pub fn format_connection_header(http_request: &HttpRequest) -> String{
+    match http_request.is_keep_alive(){
+        true => "Connection: keep-alive".to_string(),
+        false => "Connection: close".to_string(),
+    }
+}
+
+pub fn http_response_with_child(stream: &mut impl Write, child: &Child, http_request: &HttpRequest, config: &Config) {
+    let status_line = format!("{} 200 OK", http_request.version);
     let config_str = serde_json::to_string(&config).unwrap();
-    let headers = format_response_headers_to_string(&config.response_headers);
+    let connection = format_connection_header(http_request);
+    let mut headers = format_response_headers_to_string(&config.response_headers);
+    if !headers.is_empty(){
+        headers.push_str("\r\n");
+    }
     let contents = json!({
         "message": config.response_message,
         "config": format!("{}", config_str),
@@ -28,19 +40,35 @@ pub fn http_response_with_child(stream: &mut TcpStream, child: &Child, http_requ
     let length = contents.len();
     let response = format!("{status_line}\r\n\
     Content-Length: {length}\r\n\
-    {headers}\r\n\
+    {connection}\r\n\
+    {headers}\
     \r\n\
     {contents}");
     stream.write_all(response.as_bytes()).unwrap()
 }
 
-pub fn http_response_with_err(stream: &mut TcpStream, err: &io::Error, http_request: &HashMap<String, String>, config: Option<&Config>) {
+pub fn http_response_with_err(stream: &mut impl Write, err: &io::Error, http_request: &HttpRequest, config: Option<&Config>) {
     let status_line = match err.kind() {
         io::ErrorKind::NotFound => {
-            format!("{} 404 Not Found", http_request.get("Version").unwrap())
+            format!("{} 404 Not Found", http_request.version)
+        },
+        io::ErrorKind::InvalidData => {
+            format!("{} 400 Bad Request", http_request.version)
+        },
+        io::ErrorKind::FileTooLarge => {
+            format!("{} 413 Payload Too Large", http_request.version)
+        },
+        io::ErrorKind::Unsupported => {
+            format!("{} 415 Unsupported Media Type", http_request.version)
+        },
+        io::ErrorKind::TimedOut => {
+            format!("{} 408 Request Timeout", http_request.version)
+        },
+        io::ErrorKind::ArgumentListTooLong => {
+            format!("{} 431 Request Header Fields Too Large", http_request.version)
         },
         _ => {
-            format!("{} 500 Internal Server Error", http_request.get("Version").unwrap())
+            format!("{} 500 Internal Server Error", http_request.version)
         }
     };
     let err_msg = err.to_string();
@@ -48,19 +76,102 @@ pub fn http_response_with_err(stream: &mut TcpStream, err: &io::Error, http_requ
         None => &err_msg,
         Some(_) => &status_line,
     };
+    let connection = format_connection_header(http_request);
+
+    let length = contents.len();
+    let response = format!("{status_line}\r\n\
+    Content-Length: {length}\r\n\
+    {connection}\r\n\
+    \r\n\
+    {contents}");
+    stream.write_all(response.as_bytes()).unwrap()
+}
+
+/// Tell a client the server is too busy to serve it, before any request is read
+pub fn respond_with_service_unavailable(stream: &mut impl Write, retry_after: u64){
+    let contents = "Service Unavailable";
+    let length = contents.len();
+    let response = format!("HTTP/1.1 503 Service Unavailable\r\n\
+    Retry-After: {retry_after}\r\n\
+    Content-Length: {length}\r\n\
+    Connection: close\r\n\
+    \r\n\
+    {contents}");
+    stream.write_all(response.as_bytes()).unwrap()
+}
 
+/// Tell a client it sends requests too often, and how many seconds to wait before the next one
+pub fn respond_with_too_many_requests(stream: &mut impl Write, http_request: &HttpRequest, retry_after: u64){
+    let status_line = format!("{} 429 Too Many Requests", http_request.version);
+    let contents = "Too Many Requests";
     let length = contents.len();
+    let connection = format_connection_header(http_request);
     let response = format!("{status_line}\r\n\
+    Retry-After: {retry_after}\r\n\
     Content-Length: {length}\r\n\
+    {connection}\r\n\
     \r\n\
     {contents}");
     stream.write_all(response.as_bytes()).unwrap()
 }
 
-pub fn respond_with_favicon(stream: &mut TcpStream){
+/// Tell a client the hook cannot be triggered with the method of the request
+pub fn respond_with_method_not_allowed(stream: &mut impl Write, http_request: &HttpRequest, allowed_methods: &[String]){
+    let status_line = format!("{} 405 Method Not Allowed", http_request.version);
+    let allow = allowed_methods.join(", ");
+    let contents = "Method Not Allowed";
+    let length = contents.len();
+    let connection = format_connection_header(http_request);
+    let response = format!("{status_line}\r\n\
+    Allow: {allow}\r\n\
+    Content-Length: {length}\r\n\
+    {connection}\r\n\
+    \r\n\
+    {contents}");
+    stream.write_all(response.as_bytes()).unwrap()
+}
+
+/// Tell a client the methods the hook can be requested with, without triggering it
+pub fn respond_with_options(stream: &mut impl Write, http_request: &HttpRequest, allowed_methods: &[String]){
+    let status_line = format!("{} 204 No Content", http_request.version);
+    let allow = allowed_methods.join(", ");
+    let connection = format_connection_header(http_request);
+    let response = format!("{status_line}\r\n\
+    Allow: {allow}\r\n\
+    {connection}\r\n\
+    \r\n");
+    stream.write_all(response.as_bytes()).unwrap()
+}
+
+/// Answer a `HEAD` request with the headers of the hook, without triggering it
+pub fn respond_with_head(stream: &mut impl Write, http_request: &HttpRequest, config: &Config){
+    let status_line = format!("{} 200 OK", http_request.version);
+    let connection = format_connection_header(http_request);
+    let mut headers = format_response_headers_to_string(&config.response_headers);
+    if !headers.is_empty(){
+        headers.push_str("\r\n");
+    }
+    let response = format!("{status_line}\r\n\
+    {connection}\r\n\
+    {headers}\
+    \r\n");
+    stream.write_all(response.as_bytes()).unwrap()
+}
+
+/// Drop the body of a buffered response, which is never sent for a `HEAD` request,
+/// while its headers, e.g. `Content-Length`, are kept as they are
+pub fn strip_response_body(response: &mut Vec<u8>){
+    if let Some(end) = response.windows(4).position(|w| w == b"\r\n\r\n") {
+        response.truncate(end + 4);
+    }
+}
+
+pub fn respond_with_favicon(stream: &mut impl Write, http_request: &HttpRequest){
+    let connection = format_connection_header(http_request);
     let response = format!("HTTP/1.1 200 OK\r\n\
     Content-Length: {}\r\n\
     Content-Type:image/x-icon\r\n\
+    {connection}\r\n\
     \r\n", FAVICON.len()); 
     Write::write_all(stream, response.as_bytes()).unwrap(); 
     // stream.write(response.as_bytes()).unwrap();
@@ -89,4 +200,17 @@ pub fn test_format_headers_to_string(){
     Accept-Ranges: bytes\r\n\
     Cache-Control: max-age=604800".to_string();
     assert_eq!(exp, headers);
-}
\ No newline at end of file
+}
+
+#[test]
+fn test_strip_response_body(){
+    let http_request = HttpRequest::new("HEAD", "/hook", "HTTP/1.1", crate::request::test_peer());
+    let mut response = Vec::new();
+    let err = io::Error::new(io::ErrorKind::NotFound, "Undefined Hook Id");
+    http_response_with_err(&mut response, &err, &http_request, None);
+    strip_response_body(&mut response);
+    let response = String::from_utf8(response).unwrap();
+    assert!(response.starts_with("HTTP/1.1 404 Not Found\r\n"));
+    assert!(response.contains("Content-Length: 17\r\n"));
+    assert!(response.ends_with("\r\n\r\n"));
+}
@@ -1,4 +1,4 @@
-use std::collections::HashMap;
+use crate::request::HttpRequest;
 use serde::{Serialize, Deserialize};
 use serde_yaml::Value;
 use super::Rule;
@@ -9,6 +9,7 @@ pub struct AndRule{
 }
 
 impl AndRule {
+    #[allow(clippy::new_ret_no_self)]
     pub fn new(conditions: Value) -> Rule{
         let and = conditions
             .as_sequence()
@@ -21,7 +22,7 @@ impl AndRule {
         Rule::And(AndRule{and})
     } 
 
-    pub fn is_matched(&self, http_request: &HashMap<String, String>) -> bool{
+    pub fn is_matched(&self, http_request: &HttpRequest) -> bool{
         self.and.iter().all(|r|r.is_matched(http_request))
     }
 }
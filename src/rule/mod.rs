@@ -1,4 +1,4 @@
-use std::collections::HashMap;
+use crate::request::HttpRequest;
 use serde::{Deserialize, Serialize};
 use serde_yaml::Value;
 
@@ -54,7 +54,7 @@ impl Rule{
         Rule::Invalid
     }
 
-    pub fn is_matched(&self, http_request: &HashMap<String, String>) -> bool{
+    pub fn is_matched(&self, http_request: &HttpRequest) -> bool{
         match self {
             Rule::Single(r) => r.is_matched(http_request),
             Rule::And(r) => r.is_matched(http_request),
@@ -63,4 +63,15 @@ impl Rule{
             Rule::Invalid => false,
         }
     }
+
+    /// Check every single rule in the tree, so that a mistake is reported when the config is loaded
+    pub fn validate(&self) -> Result<(), String>{
+        match self {
+            Rule::Single(r) => r.validate(),
+            Rule::And(r) => r.and.iter().try_for_each(Rule::validate),
+            Rule::Or(r) => r.or.iter().try_for_each(Rule::validate),
+            Rule::Not(r) => r.not.validate(),
+            Rule::Invalid => Ok(()),
+        }
+    }
 }
@@ -1,4 +1,4 @@
-use std::collections::HashMap;
+use crate::request::HttpRequest;
 use serde::{Serialize, Deserialize};
 use serde_yaml::Value;
 use super::Rule;
@@ -9,12 +9,13 @@ pub struct NotRule{
 }
 
 impl NotRule {
+    #[allow(clippy::new_ret_no_self)]
     pub fn new(val: Value) -> Rule{
         let not = Rule::new(&val);
         Rule::Not(NotRule{not: Box::new(not)})
     }
 
-    pub fn is_matched(&self, http_request: &HashMap<String, String>) -> bool {
+    pub fn is_matched(&self, http_request: &HttpRequest) -> bool {
         !self.not.is_matched(http_request)
     }
 }
@@ -1,4 +1,4 @@
-use std::collections::HashMap;
+use crate::request::HttpRequest;
 
 use serde::{Serialize, Deserialize};
 use serde_yaml::Value;
@@ -11,6 +11,7 @@ pub struct OrRule{
 }
 
 impl OrRule {
+    #[allow(clippy::new_ret_no_self)]
     pub fn new(conditions: Value) -> Rule{
         let or = conditions
             .as_sequence()
@@ -23,7 +24,7 @@ impl OrRule {
         Rule::Or(OrRule{or})
     } 
 
-    pub fn is_matched(&self, http_request: &HashMap<String, String>) -> bool {
+    pub fn is_matched(&self, http_request: &HttpRequest) -> bool {
         self.or.iter().any(|r|r.is_matched(http_request))
     }
 }
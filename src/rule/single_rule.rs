@@ -1,11 +1,11 @@
-use std::{collections::HashMap, vec, net::{IpAddr, Ipv4Addr}, error::Error, fs};
+use std::{vec, net::IpAddr, error::Error, fs};
 
 use ipnet::IpNet;
 use itertools::Itertools;
 use serde::{Serialize, Deserialize};
 use crypto::{sha1::Sha1, hmac::Hmac, mac::Mac, sha2::{Sha256, Sha512}};
 
-use crate::{parser::*, config::configs::CONFIGS};
+use crate::{parser::*, config::configs::CONFIGS, request::HttpRequest};
 use regex::Regex;
 
 use super::Rule;
@@ -21,6 +21,10 @@ pub struct SingleRule {
 
     #[serde(default = "SingleRule::default_name")]
     pub name: String,
+
+    /// which value of a repeated header to match: `first`, `last`, `all` or a zero-based index
+    #[serde(default = "SingleRule::default_select")]
+    pub select: String,
 }
 
 impl SingleRule {
@@ -32,7 +36,11 @@ impl SingleRule {
         String::new()
     }
 
-    pub fn is_matched(&self, http_request: &HashMap<String, String>) -> bool {
+    pub fn default_select() -> String{
+        String::from("all")
+    }
+
+    pub fn is_matched(&self, http_request: &HttpRequest) -> bool {
         match self.kind.as_str(){
             "value" => self.match_value(http_request),
             "regex" => self.match_regex(http_request),
@@ -40,6 +48,8 @@ impl SingleRule {
             "hmac-sha256" => self.match_hmac_sha256(http_request),
             "hmac-sha512" => self.match_hmac_sha512(http_request),
             "ip-whitelist" => self.match_ip_whitelist(http_request),
+            "client-cert-subject" => self.match_client_cert_subject(http_request),
+            "client-cert-fingerprint" => self.match_client_cert_fingerprint(http_request),
             "include" => {
                 // read rule from the file specified by value
                 match get_rule_from_file(&self.value){
@@ -51,22 +61,24 @@ impl SingleRule {
         }
     }
 
-    fn get_value_from_source(&self, http_request: &HashMap<String, String>) -> Option<String>{
+    fn get_value_from_source(&self, http_request: &HttpRequest) -> Option<String>{
         match self.source.as_str(){
-            "header" => get_header_from_http_request(self.name.as_str(), http_request),
+            "header" => get_header_from_http_request(self.name.as_str(), &self.select, http_request),
+            "trailer" => get_trailer_from_http_request(self.name.as_str(), &self.select, http_request),
             "payload" => get_payload_item_from_http_request(&self.name, http_request),
+            "request" => http_request.attribute(&self.name),
             _ => None
         }
     }
 
-    fn match_value(&self, http_request: &HashMap<String, String>) -> bool{
+    fn match_value(&self, http_request: &HttpRequest) -> bool{
         match self.get_value_from_source(http_request) {
             Some(r) => r == self.value,
             None => false
         }  
     }
 
-    fn match_regex(&self, http_request: &HashMap<String, String>) -> bool {
+    fn match_regex(&self, http_request: &HttpRequest) -> bool {
         match self.get_value_from_source(http_request) {
             Some(r) => {
                 let exp = self.value.to_string();
@@ -84,10 +96,10 @@ impl SingleRule {
         }  
     }
 
-    fn match_hmac_sha1(&self, http_request: &HashMap<String, String>) -> bool {
-        let payload = http_request.get("Body");
+    fn match_hmac_sha1(&self, http_request: &HttpRequest) -> bool {
+        let payload = http_request.body.as_deref();
         let secret = &self.value;
-        let signature = match get_header_from_http_request(self.name.as_str(), http_request){
+        let signature = match get_header_from_http_request(self.name.as_str(), &self.select, http_request){
             None => {
                 let msg = format!("Header [{}] not found in request headers", self.name);
                 log::warn!("{}", msg);
@@ -98,10 +110,10 @@ impl SingleRule {
         check_payload_signature(payload, secret, signature.as_str())
     }
 
-    fn match_hmac_sha256(&self, http_request: &HashMap<String, String>) -> bool {
-        let payload = http_request.get("Body");
+    fn match_hmac_sha256(&self, http_request: &HttpRequest) -> bool {
+        let payload = http_request.body.as_deref();
         let secret = &self.value;
-        let signature = match get_header_from_http_request(self.name.as_str(), http_request){
+        let signature = match get_header_from_http_request(self.name.as_str(), &self.select, http_request){
             None => {
                 let msg = format!("Header [{}] not found in request headers", self.name);
                 log::warn!("{}", msg);
@@ -112,10 +124,10 @@ impl SingleRule {
         check_payload_signature256(payload, secret, signature.as_str())
     }
 
-    fn match_hmac_sha512(&self, http_request: &HashMap<String, String>) -> bool {
-        let payload = http_request.get("Body");
+    fn match_hmac_sha512(&self, http_request: &HttpRequest) -> bool {
+        let payload = http_request.body.as_deref();
         let secret = &self.value;
-        let signature = match get_header_from_http_request(self.name.as_str(), http_request){
+        let signature = match get_header_from_http_request(self.name.as_str(), &self.select, http_request){
             None => {
                 let msg = format!("Header [{}] not found in request headers", self.name);
                 log::warn!("{}", msg);
@@ -126,38 +138,76 @@ impl SingleRule {
         check_payload_signature512(payload, secret, signature.as_str())    
     }
 
-    fn match_ip_whitelist(&self, http_request: &HashMap<String, String>) -> bool {
-        let peer_address = http_request.get("Peer-Address").unwrap();
-        let ip = match peer_address.split(':').collect::<Vec<&str>>()[0].parse::<IpAddr>(){
-            Ok(r) => r,
-            Err(e) => {
-                let msg = format!("Invalid Peer Address {}: {}", peer_address, e);
+    fn match_ip_whitelist(&self, http_request: &HttpRequest) -> bool {
+        let ip = match http_request.client_ip{
+            Some(ip) => ip,
+            None => {
+                let msg = format!("No client IP address for peer {}", http_request.peer.address);
                 log::warn!("{}", msg);
                 return false;
             }
         };
 
-        let ip_ranges: Vec<String> = if self.value.contains(','){
-            extract_comma_separated_values(&self.value, "")
-        }else{
-            vec![(*self.value).to_string()]
+        // rules in the config are checked at load, but an included rule file is only read here
+        let ip_ranges = match parse_ip_whitelist(&self.value){
+            Ok(r) => r,
+            Err(e) => {
+                log::error!("{}", e);
+                return false;
+            }
         };
-
+        // an IPv4-mapped IPv6 address, e.g. from a dual-stack socket, is matched as the IPv4 address
+        let ip = ip.to_canonical();
         ip_ranges
         .iter()
-        .map(|cidr|{
-            match cidr.parse::<IpNet>(){
-                Ok(r) => r,
-                Err(e) => {
-                    let msg = format!("Invalid IP CIDR {}: {}", cidr, e);
-                    log::error!("{}", msg);
-                    IpNet::new(Ipv4Addr::new(0, 0, 0, 0).into(), 32).unwrap()
-                }
+        .any(|x|x.contains(&ip))
+    }
+
+    /// Check the parts of the rule that can be told invalid without a request
+    pub fn validate(&self) -> Result<(), String>{
+        match self.kind.as_str(){
+            "ip-whitelist" => parse_ip_whitelist(&self.value).map(|_| ()),
+            "client-cert-subject" => parse_subject(&self.value).map(|_| ()),
+            _ => Ok(()),
+        }
+    }
+
+    fn match_client_cert_subject(&self, http_request: &HttpRequest) -> bool {
+        let attributes = match &http_request.peer.client_cert{
+            Some(c) => &c.subject_attributes,
+            None => {
+                log::warn!("No verified client certificate in request");
+                return false;
             }
-        })
-        .collect::<Vec<IpNet>>()
-        .iter()
-        .any(|x|x.contains(&ip))        
+        };
+        let expected = match parse_subject(&self.value) {
+            Ok(e) => e,
+            Err(e) => {
+                log::error!("{}", e);
+                return false;
+            }
+        };
+        let matches = |(t1, v1): &(String, String), (t2, v2): &(String, String)| t1.eq_ignore_ascii_case(t2) && v1 == v2;
+        // match either a single attribute of the subject, e.g. `CN=sender`, or all of them in order
+        match expected.as_slice() {
+            [attr] => attributes.iter().any(|a| matches(a, attr)),
+            _ => expected.len() == attributes.len() && expected.iter().zip(attributes.iter()).all(|(e, a)| matches(a, e)),
+        }
+    }
+
+    fn match_client_cert_fingerprint(&self, http_request: &HttpRequest) -> bool {
+        let fingerprint = match &http_request.peer.client_cert{
+            Some(c) => &c.fingerprint,
+            None => {
+                log::warn!("No verified client certificate in request");
+                return false;
+            }
+        };
+        // fingerprints are accepted with or without colons, in any case
+        extract_comma_separated_values(&self.value, "")
+            .iter()
+            .map(|f| f.replace(':', "").to_lowercase())
+            .any(|f| f == *fingerprint)
     }
 }
 
@@ -182,7 +232,7 @@ fn get_rule_from_file(rule_file: &str) -> Result<Rule, Box<dyn Error>> {
     Ok(rule)
 }
 
-fn check_payload_signature(payload: Option<&String>, secret: &str, signature: &str)-> bool{
+fn check_payload_signature(payload: Option<&[u8]>, secret: &str, signature: &str)-> bool{
     if secret.is_empty() {
         let err_msg = "signature validation secret can not be empty".to_string();
         log::error!("{}", err_msg);
@@ -194,7 +244,7 @@ fn check_payload_signature(payload: Option<&String>, secret: &str, signature: &s
 	validate_mac(payload, &mut Hmac::new(Sha1::new(), secret.as_bytes()), signatures)
 }
 
-fn check_payload_signature256(payload: Option<&String>, secret: &str, signature: &str)-> bool{
+fn check_payload_signature256(payload: Option<&[u8]>, secret: &str, signature: &str)-> bool{
     if secret.is_empty() {
         let err_msg = "signature validation secret can not be empty".to_string();
         log::error!("{}", err_msg);
@@ -206,7 +256,7 @@ fn check_payload_signature256(payload: Option<&String>, secret: &str, signature:
 	validate_mac(payload, &mut Hmac::new(Sha256::new(), secret.as_bytes()), signatures)
 }
 
-fn check_payload_signature512(payload: Option<&String>, secret: &str, signature: &str)-> bool{
+fn check_payload_signature512(payload: Option<&[u8]>, secret: &str, signature: &str)-> bool{
     if secret.is_empty() {
         let err_msg = "signature validation secret can not be empty".to_string();
         log::error!("{}", err_msg);
@@ -225,6 +275,63 @@ fn extract_signatures(source: &str, prefix: &str) -> Vec<String> {
     vec![source.trim_start_matches(prefix).to_string()]
 }
 
+/// Parse the comma separated ranges of an `ip-whitelist` rule.
+/// A single address stands for itself, and the zone ID of an IPv6 address, e.g. `fe80::1%eth0`,
+/// is dropped since the client address carries none.
+/// IPv4-mapped IPv6 ranges, e.g. `::ffff:192.0.2.0/120`, are turned into IPv4 ones.
+fn parse_ip_whitelist(value: &str) -> Result<Vec<IpNet>, String>{
+    extract_comma_separated_values(value, "")
+    .iter()
+    .map(|cidr|{
+        let addr = match cidr.split_once('%') {
+            Some((addr, zone)) => match zone.split_once('/') {
+                Some((_, prefix)) => format!("{}/{}", addr, prefix),
+                None => addr.to_string(),
+            },
+            None => cidr.to_string(),
+        };
+        let net = match addr.parse::<IpNet>(){
+            Ok(r) => r,
+            Err(e) => match addr.parse::<IpAddr>() {
+                Ok(ip) => IpNet::from(ip),
+                Err(_) => return Err(format!("Invalid IP CIDR [{}] in ip-whitelist: {}", cidr, e)),
+            },
+        };
+        match net {
+            IpNet::V6(n) if n.prefix_len() >= 96 => match n.addr().to_ipv4_mapped() {
+                Some(v4) => Ok(IpNet::new(IpAddr::V4(v4), n.prefix_len() - 96).unwrap()),
+                None => Ok(net),
+            },
+            _ => Ok(net),
+        }
+    })
+    .collect()
+}
+
+/// Parse the attributes of a `client-cert-subject` rule, e.g. `CN=sender, O=Example`.
+/// Attributes are separated by `,`, or by `+` within a multi-valued RDN,
+/// and a separator inside a value is escaped with `\`, e.g. `O=Example\, Inc.`
+fn parse_subject(value: &str) -> Result<Vec<(String, String)>, String>{
+    let mut attributes = Vec::new();
+    let mut current = String::new();
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => current.extend(chars.next()),
+            ',' | '+' => attributes.push(std::mem::take(&mut current)),
+            c => current.push(c),
+        }
+    }
+    attributes.push(current);
+    attributes
+        .iter()
+        .map(|attr| match attr.split_once('=') {
+            Some((t, v)) if !t.trim().is_empty() => Ok((t.trim().to_string(), v.trim().to_string())),
+            _ => Err(format!("Invalid attribute [{}] in client-cert-subject [{}]", attr.trim(), value)),
+        })
+        .collect()
+}
+
 fn extract_comma_separated_values(source: &str, prefix: &str) -> Vec<String>{
     let values: Vec<String> = source.split(',')
         .map(|s|s.trim())
@@ -234,14 +341,14 @@ fn extract_comma_separated_values(source: &str, prefix: &str) -> Vec<String>{
     values
 }
 
-fn validate_mac<T>(payload: Option<&String>, hasher: &mut T, signatures: Vec<String>) -> bool
+fn validate_mac<T>(payload: Option<&[u8]>, hasher: &mut T, signatures: Vec<String>) -> bool
 where T: Mac{
     if payload.is_none(){
         let msg = "HMAC validation failed due to empty payload !".to_string();
         log::warn!("{}", msg);
         return false;
     }
-    hasher.input(payload.unwrap().as_bytes());
+    hasher.input(payload.unwrap());
     let actual_mac = hasher.result();
     let expected_signature = actual_mac
                                     .code()
@@ -256,31 +363,34 @@ where T: Mac{
               .any(|x| *x)
 }
 
+#[cfg(test)]
+fn test_request(peer_address: &str) -> HttpRequest{
+    let mut peer = crate::request::test_peer();
+    peer.address = crate::stream::PeerAddress::Tcp(peer_address.parse().unwrap());
+    let mut request = HttpRequest::new("POST", "/webhook-test-1", "HTTP/1.1", peer);
+    request.headers.append("User-Agent", "curl/7.77.0");
+    request.headers.append("Accept", "*/*");
+    request.headers.append("Host", "127.0.0.1:7878");
+    request.headers.append("Content-Type", "application/json");
+    request.headers.append("Content-Length", "45");
+    request.body = Some(b"{\"data\":{\"data2\":[\"val1\", \"val2\"], \"data3\": \"val3\"},\"data_s\":\"s_d\"}".to_vec());
+    request
+}
+
 #[test]
 fn test_match_hmac_sha1(){
-    let mut request: HashMap<String, String> = HashMap::from([
-        ("Url".to_string(), "/webhook-test-1".to_string()),
-        ("User-Agent".to_string(), "curl/7.77.0".to_string()),
-        ("Version".to_string(), "HTTP/1.1".to_string()),
-        ("Method".to_string(), "POST".to_string()),
-        ("Accept".to_string(), "*/*".to_string()),
-        ("Host".to_string(), "127.0.0.1:7878".to_string()),
-        ("Content-Type".to_string(), "application/json".to_string()),
-        ("Content-Length".to_string(), "45".to_string()),
-        ("Body".to_string(), "{\"data\":{\"data2\":[\"val1\", \"val2\"], \"data3\": \"val3\"},\"data_s\":\"s_d\"}".to_string()),
-        ("Peer-Address".to_string(), "127.0.0.1:56020".to_string()),
-        ("X-Signature".to_string(), "".to_string()),
-    ]);
+    let mut request = test_request("127.0.0.1:56020");
 
     let single_rule = SingleRule{
         kind: "hmac-sha1".to_string(),
         value: "1234".to_string(),
         source: "header".to_string(),
         name: "X-Signature".to_string(),
+        select: SingleRule::default_select(),
     };
 
     let mut hasher = Hmac::new(Sha1::new(), single_rule.value.as_bytes());
-    hasher.input(request.get("Body").unwrap().as_bytes());
+    hasher.input(request.body.as_deref().unwrap());
     let actual_mac = hasher.result();
     let actual_mac = actual_mac
         .code()
@@ -288,7 +398,7 @@ fn test_match_hmac_sha1(){
         .format_with("", |byte, f| f(&format_args!("{:02x}", byte)))
         .to_string();
     let sig = format!("sha1=dcwwcwee, sha1={}", actual_mac);
-    request.insert("X-Signature".to_string(), sig.to_string());
+    request.headers.set("X-Signature", &sig);
     assert!(single_rule.match_hmac_sha1(&request));
 }
 
@@ -310,48 +420,82 @@ fn test_extract_comma_separated_values(){
 
 #[test]
 fn test_match_ip_white_list(){
-    let request: HashMap<String, String> = HashMap::from([
-        ("Url".to_string(), "/webhook-test-1".to_string()),
-        ("User-Agent".to_string(), "curl/7.77.0".to_string()),
-        ("Version".to_string(), "HTTP/1.1".to_string()),
-        ("Method".to_string(), "POST".to_string()),
-        ("Accept".to_string(), "*/*".to_string()),
-        ("Host".to_string(), "127.0.0.1:7878".to_string()),
-        ("Content-Type".to_string(), "application/json".to_string()),
-        ("Content-Length".to_string(), "45".to_string()),
-        ("Body".to_string(), "{\"data\":{\"data2\":[\"val1\", \"val2\"], \"data3\": \"val3\"},\"data_s\":\"s_d\"}".to_string()),
-        ("Peer-Address".to_string(), "10.0.2.6:56020".to_string()),
-    ]);
+    let request = test_request("10.0.2.6:56020");
 
     let single_rule = SingleRule{
         kind: "ip-whitelist".to_string(),
         value: "10.0.1.2/24, 10.0.2.5/24, 10.0.0.1/32".to_string(),
         source: "".to_string(),
         name: "".to_string(),
+        select: SingleRule::default_select(),
     };
     assert!(single_rule.match_ip_whitelist(&request));
+
+    // behind a trusted proxy the client address is matched instead of the peer's
+    let mut request = test_request("10.0.2.6:56020");
+    request.client_ip = Some("203.0.113.7".parse().unwrap());
+    assert!(!single_rule.match_ip_whitelist(&request));
+    request.client_ip = None;
+    assert!(!single_rule.match_ip_whitelist(&request));
+
+    // an IPv4 peer on a dual-stack socket
+    request.client_ip = Some("::ffff:10.0.0.1".parse().unwrap());
+    assert!(single_rule.match_ip_whitelist(&request));
+
+    let single_rule = SingleRule{
+        kind: "ip-whitelist".to_string(),
+        value: "2001:db8::/32, fe80::%eth0/64, ::1, ::ffff:192.0.2.0/120".to_string(),
+        source: "".to_string(),
+        name: "".to_string(),
+        select: SingleRule::default_select(),
+    };
+    assert!(single_rule.validate().is_ok());
+    for ip in ["2001:db8:cafe::17", "fe80::1", "::1", "192.0.2.9"]{
+        request.client_ip = Some(ip.parse().unwrap());
+        assert!(single_rule.match_ip_whitelist(&request), "{}", ip);
+    }
+    request.client_ip = Some("10.0.0.1".parse().unwrap());
+    assert!(!single_rule.match_ip_whitelist(&request));
+
+    let single_rule = SingleRule{
+        kind: "ip-whitelist".to_string(),
+        value: "10.0.0.0/8, 10.0.0.300/32".to_string(),
+        source: "".to_string(),
+        name: "".to_string(),
+        select: SingleRule::default_select(),
+    };
+    assert!(single_rule.validate().is_err());
+    assert!(!single_rule.match_ip_whitelist(&request));
+}
+
+#[test]
+fn test_match_request_attribute(){
+    let mut request = test_request("10.0.0.2:56020");
+    request.client_ip = Some("203.0.113.7".parse().unwrap());
+
+    let single_rule = SingleRule{
+        kind: "regex".to_string(),
+        value: "^203\\.0\\.113\\.".to_string(),
+        source: "request".to_string(),
+        name: "Client-IP".to_string(),
+        select: SingleRule::default_select(),
+    };
+    assert!(single_rule.is_matched(&request));
+    request.headers.append("Client-IP", "203.0.113.8");
+    request.client_ip = None;
+    assert!(!single_rule.is_matched(&request));
 }
 
 #[test]
 fn test_match_value(){
-    let request: HashMap<String, String> = HashMap::from([
-        ("Url".to_string(), "/webhook-test-1".to_string()),
-        ("User-Agent".to_string(), "curl/7.77.0".to_string()),
-        ("Version".to_string(), "HTTP/1.1".to_string()),
-        ("Method".to_string(), "POST".to_string()),
-        ("Accept".to_string(), "*/*".to_string()),
-        ("Host".to_string(), "127.0.0.1:7878".to_string()),
-        ("Content-Type".to_string(), "application/json".to_string()),
-        ("Content-Length".to_string(), "45".to_string()),
-        ("Body".to_string(), "{\"data\":{\"data2\":[\"val1\", \"val2\"], \"data3\": \"val3\"},\"data_s\":\"s_d\"}".to_string()),
-        ("Peer-Address".to_string(), "127.0.0.1:56020".to_string()),
-    ]);
+    let request = test_request("127.0.0.1:56020");
 
     let single_rule = SingleRule{
         kind: "value".to_string(),
         value: "127.0.0.1:7878".to_string(),
         source: "header".to_string(),
         name: "Host".to_string(),
+        select: SingleRule::default_select(),
     };
     assert!(single_rule.match_value(&request));
 
@@ -360,30 +504,21 @@ fn test_match_value(){
         value: "val1".to_string(),
         source: "payload".to_string(),
         name: "data.data2.0".to_string(),
+        select: SingleRule::default_select(),
     };
     assert!(single_rule.match_value(&request));
 }
 
 #[test]
 fn test_match_regex(){
-    let request: HashMap<String, String> = HashMap::from([
-        ("Url".to_string(), "/webhook-test-1".to_string()),
-        ("User-Agent".to_string(), "curl/7.77.0".to_string()),
-        ("Version".to_string(), "HTTP/1.1".to_string()),
-        ("Method".to_string(), "POST".to_string()),
-        ("Accept".to_string(), "*/*".to_string()),
-        ("Host".to_string(), "127.0.0.1:7878".to_string()),
-        ("Content-Type".to_string(), "application/json".to_string()),
-        ("Content-Length".to_string(), "45".to_string()),
-        ("Body".to_string(), "{\"data\":{\"data2\":[\"val1\", \"val2\"], \"data3\": \"val3\"},\"data_s\":\"s_d\"}".to_string()),
-        ("Peer-Address".to_string(), "127.0.0.1:56020".to_string()),
-    ]);
+    let request = test_request("127.0.0.1:56020");
 
     let single_rule = SingleRule{
         kind: "regex".to_string(),
         value: "127.0.0.1:i*".to_string(),
         source: "header".to_string(),
         name: "Host".to_string(),
+        select: SingleRule::default_select(),
     };
     assert!(single_rule.match_regex(&request));
 
@@ -392,6 +527,7 @@ fn test_match_regex(){
         value: "127.0.0.1:ii*".to_string(),
         source: "header".to_string(),
         name: "Host".to_string(),
+        select: SingleRule::default_select(),
     };
     assert!(!single_rule.match_regex(&request));
 }
@@ -408,3 +544,100 @@ fn test_get_rule_from_file(){
     let rule = get_rule_from_file(rule_file);
     println!("{:#?}", rule);
 }
+
+#[test]
+fn test_match_client_cert(){
+    let mut request = HttpRequest::new("POST", "/webhook-test-1", "HTTP/1.1", crate::request::test_peer());
+
+    let subject_rule = SingleRule{
+        kind: "client-cert-subject".to_string(),
+        value: "CN=alert-sender".to_string(),
+        source: "".to_string(),
+        name: "".to_string(),
+        select: SingleRule::default_select(),
+    };
+    let fingerprint_rule = SingleRule{
+        kind: "client-cert-fingerprint".to_string(),
+        value: "00:11, AB:CD:EF:01".to_string(),
+        source: "".to_string(),
+        name: "".to_string(),
+        select: SingleRule::default_select(),
+    };
+    assert!(!subject_rule.is_matched(&request));
+    assert!(!fingerprint_rule.is_matched(&request));
+
+    let attributes = |attrs: &[(&str, &str)]| attrs.iter().map(|(t, v)| (t.to_string(), v.to_string())).collect();
+    request.peer.client_cert = Some(crate::tls::ClientCert {
+        subject: "CN=alert-sender, O=Example".to_string(),
+        subject_attributes: attributes(&[("CN", "alert-sender"), ("O", "Example")]),
+        fingerprint: "abcdef01".to_string(),
+    });
+    assert!(subject_rule.is_matched(&request));
+    assert!(fingerprint_rule.is_matched(&request));
+    let whole_subject_rule = SingleRule{ value: "cn=alert-sender, O=Example".to_string(), ..subject_rule.clone() };
+    assert!(whole_subject_rule.is_matched(&request));
+
+    // a comma inside a value does not make the rest of it an attribute
+    request.peer.client_cert = Some(crate::tls::ClientCert {
+        subject: "O=x, CN=alert-sender".to_string(),
+        subject_attributes: attributes(&[("O", "x, CN=alert-sender")]),
+        fingerprint: "abcdef01".to_string(),
+    });
+    assert!(!subject_rule.is_matched(&request));
+    assert!(!whole_subject_rule.is_matched(&request));
+    let escaped_rule = SingleRule{ value: "O=x\\, CN=alert-sender".to_string(), ..subject_rule.clone() };
+    assert!(escaped_rule.is_matched(&request));
+
+    // so does a multi-valued RDN
+    request.peer.client_cert = Some(crate::tls::ClientCert {
+        subject: "CN=alert-sender + O=Example".to_string(),
+        subject_attributes: attributes(&[("CN", "alert-sender"), ("O", "Example")]),
+        fingerprint: "abcdef01".to_string(),
+    });
+    assert!(subject_rule.is_matched(&request));
+    assert!(SingleRule{ value: "CN=alert-sender + O=Example".to_string(), ..subject_rule.clone() }.is_matched(&request));
+    assert!(SingleRule{ value: "alert-sender".to_string(), ..subject_rule.clone() }.validate().is_err());
+}
+
+#[test]
+fn test_match_repeated_header(){
+    let mut request = test_request("127.0.0.1:56020");
+    request.headers.append("x-hub-signature", "sha1=first");
+    request.headers.append("X-Hub-Signature", "sha1=second");
+
+    let mut single_rule = SingleRule{
+        kind: "value".to_string(),
+        value: "sha1=first".to_string(),
+        source: "header".to_string(),
+        name: "X-Hub-Signature".to_string(),
+        select: "first".to_string(),
+    };
+    assert!(single_rule.is_matched(&request));
+    single_rule.select = "last".to_string();
+    assert!(!single_rule.is_matched(&request));
+    single_rule.select = "1".to_string();
+    single_rule.value = "sha1=second".to_string();
+    assert!(single_rule.is_matched(&request));
+    single_rule.select = SingleRule::default_select();
+    single_rule.value = "sha1=first, sha1=second".to_string();
+    assert!(single_rule.is_matched(&request));
+}
+
+#[test]
+fn test_match_hmac_binary_payload(){
+    let mut request = test_request("127.0.0.1:56020");
+    request.body = Some(vec![0x1f, 0x8b, 0x08, 0x00, 0xff, 0xfe, 0x00, 0x80]);
+    let mut hasher = Hmac::new(Sha256::new(), b"1234");
+    hasher.input(request.body.as_deref().unwrap());
+    let signature = hasher.result().code().iter().format_with("", |byte, f| f(&format_args!("{:02x}", byte))).to_string();
+    request.headers.append("X-Signature", &format!("sha256={}", signature));
+
+    let single_rule = SingleRule{
+        kind: "hmac-sha256".to_string(),
+        value: "1234".to_string(),
+        source: "header".to_string(),
+        name: "X-Signature".to_string(),
+        select: SingleRule::default_select(),
+    };
+    assert!(single_rule.is_matched(&request));
+}
@@ -0,0 +1,550 @@
+use std::{fs, future::Future, io, mem, os::{fd::OwnedFd, unix::fs::FileTypeExt}, sync::{Arc, Mutex, atomic::{AtomicU64, AtomicUsize, Ordering}}, time::Duration};
+
+use tokio::{io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt}, net::{TcpListener, UnixListener}, sync::{OwnedSemaphorePermit, Semaphore}, task::JoinSet, time::Instant};
+use tokio_rustls::TlsAcceptor;
+
+use crate::{
+    config::{configs::CONFIGS, listener::ListenerConfig},
+    handle_connection,
+    proxy::read_proxy_header,
+    response::respond_with_service_unavailable,
+    shutdown::Shutdown,
+    stream::{Peer, PeerAddress, with_deadline, with_timeout},
+    tls::ClientCert,
+};
+
+/// A socket from which the server accepts connections
+pub enum Listener {
+    Tcp(TcpListener),
+    /// the unix domain socket listener, and the path it is bound to
+    Unix(UnixListener, String),
+}
+
+impl Listener {
+    /// Take over a listening socket opened by someone else, e.g. the service manager
+    pub fn from_fd(fd: OwnedFd) -> Result<Self, io::Error>{
+        // a tcp socket has no unix socket address, and vice versa
+        let listener = std::net::TcpListener::from(fd);
+        if listener.local_addr().is_ok() {
+            listener.set_nonblocking(true)?;
+            return Ok(Listener::Tcp(TcpListener::from_std(listener)?));
+        }
+        let listener = std::os::unix::net::UnixListener::from(OwnedFd::from(listener));
+        let path = match listener.local_addr()?.as_pathname() {
+            Some(p) => p.to_string_lossy().to_string(),
+            None => String::new(),
+        };
+        listener.set_nonblocking(true)?;
+        Ok(Listener::Unix(UnixListener::from_std(listener)?, path))
+    }
+}
+
+/// A listener ready to accept connections, with the tls settings of its config
+pub struct BoundListener {
+    pub name: String,
+    pub listener: Listener,
+    pub tls_acceptor: Option<TlsAcceptor>,
+    /// whether a PROXY protocol header precedes every connection
+    pub proxy_protocol: bool,
+    /// whether the socket is passed in rather than bound by rusthook, so its socket file is left in place
+    pub activated: bool,
+}
+
+impl BoundListener {
+    /// Bind the address of the listener config, must be called within the runtime
+    pub fn bind(config: &ListenerConfig, tls_acceptor: Option<&TlsAcceptor>) -> Result<Self, io::Error>{
+        let tls_acceptor = Self::get_tls_acceptor(config, tls_acceptor)?;
+        let listener = match config.get_unix_path() {
+            Some(path) => {
+                remove_stale_socket(path)?;
+                let listener = std::os::unix::net::UnixListener::bind(path)?;
+                listener.set_nonblocking(true)?;
+                Listener::Unix(UnixListener::from_std(listener)?, path.to_string())
+            },
+            None => {
+                let listener = std::net::TcpListener::bind(&config.address)?;
+                listener.set_nonblocking(true)?;
+                Listener::Tcp(TcpListener::from_std(listener)?)
+            }
+        };
+        Ok(BoundListener { name: config.name.clone(), listener, tls_acceptor, proxy_protocol: config.proxy_protocol, activated: false })
+    }
+
+    /// Serve the listener config on a socket passed by systemd instead of binding its address,
+    /// must be called within the runtime
+    pub fn from_fd(config: &ListenerConfig, fd: OwnedFd, tls_acceptor: Option<&TlsAcceptor>) -> Result<Self, io::Error>{
+        let tls_acceptor = Self::get_tls_acceptor(config, tls_acceptor)?;
+        let listener = Listener::from_fd(fd)?;
+        log::info!("Listener [{}] takes over the socket passed by systemd", config.name);
+        Ok(BoundListener { name: config.name.clone(), listener, tls_acceptor, proxy_protocol: config.proxy_protocol, activated: true })
+    }
+
+    fn get_tls_acceptor(config: &ListenerConfig, tls_acceptor: Option<&TlsAcceptor>) -> Result<Option<TlsAcceptor>, io::Error>{
+        match (config.tls, tls_acceptor) {
+            (false, _) => Ok(None),
+            (true, Some(t)) => Ok(Some(t.clone())),
+            (true, None) => {
+                let err_msg = format!("TLS is enabled on listener [{}] without a certificate configured", config.name);
+                log::error!("{}", err_msg);
+                Err(io::Error::new(io::ErrorKind::InvalidInput, err_msg))
+            }
+        }
+    }
+}
+
+/// A socket file left by a previous run would make binding fail
+fn remove_stale_socket(path: &str) -> Result<(), io::Error>{
+    match fs::metadata(path) {
+        Ok(m) if m.file_type().is_socket() => {
+            log::info!("Removing stale socket file {}", path);
+            fs::remove_file(path)
+        },
+        _ => Ok(()),
+    }
+}
+
+/// Limits on the connections the server takes at the same time
+#[derive(Debug, Clone, Copy)]
+pub struct ConnectionLimits {
+    /// connections served at the same time
+    pub max_connections: usize,
+    /// accepted connections waiting for a running one to finish, more are answered with 503 right away
+    pub max_queued_connections: usize,
+    /// seconds sent in `Retry-After` with a 503
+    pub retry_after: u64,
+}
+
+/// Admission of accepted connections, shared by all the listeners
+struct ConnectionQueue {
+    /// every connection being served holds a permit
+    permits: Arc<Semaphore>,
+    limits: ConnectionLimits,
+    /// connections waiting for a permit
+    queued: AtomicUsize,
+    /// connections answered with 503 since the server started
+    rejected: AtomicU64,
+    /// the tasks of the connections, aborted if they outlive the shutdown grace period
+    tasks: Mutex<JoinSet<()>>,
+}
+
+/// What a connection needs to know about the listener which accepted it
+#[derive(Clone)]
+struct ConnectionContext {
+    listener: String,
+    tls_acceptor: Option<TlsAcceptor>,
+    proxy_protocol: bool,
+    shutdown: Shutdown,
+}
+
+impl ConnectionQueue {
+    /// Serve the connection right away if there is room, otherwise queue it,
+    /// or answer it with 503 if the queue is full as well
+    fn dispatch<S>(self: &Arc<Self>, sock: S, address: PeerAddress, context: &ConnectionContext)
+    where S: AsyncRead + AsyncWrite + Unpin + Send + 'static{
+        if let Ok(permit) = self.permits.clone().try_acquire_owned() {
+            self.spawn(serve_connection(sock, address, context.clone(), permit));
+            return;
+        }
+        let max_queued = self.limits.max_queued_connections;
+        let queued = match self.queued.fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| (n < max_queued).then_some(n + 1)) {
+            Ok(n) => n + 1,
+            Err(n) => {
+                let rejected = self.rejected.fetch_add(1, Ordering::Relaxed) + 1;
+                log::warn!(
+                    "Too busy, connection from {} on listener [{}] rejected: {} queued, {} rejected in total",
+                    address, context.listener, n, rejected
+                );
+                self.spawn(reject_connection(sock, address, context.clone(), self.limits.retry_after));
+                return;
+            }
+        };
+        log::debug!("Connection from {} queued, {} connection(s) waiting", address, queued);
+
+        let queue = self.clone();
+        let context = context.clone();
+        self.spawn(async move {
+            let mut shutdown = context.shutdown.clone();
+            let permit = tokio::select! {
+                permit = queue.permits.clone().acquire_owned() => permit.ok(),
+                // a connection not served yet is dropped on shutdown
+                _ = shutdown.triggered() => None,
+            };
+            queue.queued.fetch_sub(1, Ordering::SeqCst);
+            if let Some(permit) = permit {
+                serve_connection(sock, address, context, permit).await;
+            }
+        });
+    }
+
+    fn spawn<F>(&self, task: F)
+    where F: Future<Output = ()> + Send + 'static{
+        let mut tasks = self.tasks.lock().unwrap();
+        // the finished tasks are reaped as new ones come, so that the set does not keep growing
+        while tasks.try_join_next().is_some() {}
+        tasks.spawn(task);
+    }
+
+    /// Abort the tasks of the connections still running, and wait for them to be dropped
+    async fn abort_all(&self){
+        let mut tasks = mem::take(&mut *self.tasks.lock().unwrap());
+        tasks.abort_all();
+        while tasks.join_next().await.is_some() {}
+    }
+}
+
+/// Accept connections from all the listeners and serve each of them in its own task.
+/// At most `max_connections` connections are served at the same time, and at most
+/// `max_queued_connections` more wait for a running one to finish. Further connections are
+/// answered with `503 Service Unavailable` right away.
+///
+/// Once `shutdown` is triggered the listeners are closed, and the running connections
+/// are given `shutdown_grace_period` to finish the requests in flight, after which they are closed.
+pub async fn serve(listeners: Vec<BoundListener>, limits: ConnectionLimits, shutdown: Shutdown){
+    let max_connections = limits.max_connections;
+    let queue = Arc::new(ConnectionQueue {
+        permits: Arc::new(Semaphore::new(max_connections)),
+        limits,
+        queued: AtomicUsize::new(0),
+        rejected: AtomicU64::new(0),
+        tasks: Mutex::new(JoinSet::new()),
+    });
+    let mut accept_tasks = JoinSet::new();
+    for listener in listeners {
+        accept_tasks.spawn(accept_connections(listener, queue.clone(), shutdown.clone()));
+    }
+    while accept_tasks.join_next().await.is_some() {}
+
+    let rejected = queue.rejected.load(Ordering::Relaxed);
+    if rejected > 0 {
+        log::info!("{} connection(s) were rejected while the server was too busy", rejected);
+    }
+    // every running connection holds a permit, all of them are back once the connections are drained
+    let permits = &queue.permits;
+    let running = max_connections - permits.available_permits();
+    if running > 0 {
+        log::info!("Waiting for {} running connection(s) to finish", running);
+    }
+    let grace_period = CONFIGS.global.get_shutdown_grace_period();
+    match tokio::time::timeout(grace_period, permits.acquire_many(max_connections as u32)).await {
+        Ok(_) => log::info!("All connections are closed"),
+        Err(_) => {
+            log::warn!(
+                "{} connection(s) still running after {:?}, closing them",
+                max_connections - permits.available_permits(), grace_period
+            );
+            queue.abort_all().await;
+        },
+    };
+}
+
+async fn accept_connections(listener: BoundListener, queue: Arc<ConnectionQueue>, mut shutdown: Shutdown){
+    let name = listener.name.clone();
+    let context = ConnectionContext {
+        listener: listener.name,
+        tls_acceptor: listener.tls_acceptor,
+        proxy_protocol: listener.proxy_protocol,
+        shutdown: shutdown.clone(),
+    };
+    log::info!("Listener [{}] started", name);
+    loop {
+        let accepted = match &listener.listener {
+            Listener::Tcp(l) => tokio::select! {
+                r = l.accept() => r.map(|(sock, addr)| queue.dispatch(sock, PeerAddress::Tcp(addr), &context)),
+                _ = shutdown.triggered() => break,
+            },
+            Listener::Unix(l, path) => tokio::select! {
+                r = l.accept() => r.map(|(sock, addr)| {
+                    // peers of a unix socket are usually unnamed, fall back to the listening path
+                    let address = match addr.as_pathname() {
+                        Some(p) => PeerAddress::Unix(p.to_string_lossy().to_string()),
+                        None => PeerAddress::Unix(path.clone()),
+                    };
+                    queue.dispatch(sock, address, &context);
+                }),
+                _ = shutdown.triggered() => break,
+            },
+        };
+        if let Err(e) = accepted {
+            // e.g. too many open files, back off instead of spinning
+            log::error!("Listener [{}] failed to accept connection: {}", name, e);
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+    }
+
+    if let (Listener::Unix(_, path), false) = (&listener.listener, listener.activated) {
+        let _ = fs::remove_file(path);
+    }
+    log::info!("Listener [{}] stopped", name);
+}
+
+/// How long a connection the server has no room for is given to send the PROXY protocol header
+/// and complete the tls handshake, so that it can be told to retry later
+const REJECT_HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Answer a connection the server has no room for with 503, over tls if the listener has it
+async fn reject_connection<S>(mut sock: S, address: PeerAddress, context: ConnectionContext, retry_after: u64)
+where S: AsyncRead + AsyncWrite + Unpin{
+    let deadline = Some(Instant::now() + REJECT_HANDSHAKE_TIMEOUT);
+    if context.proxy_protocol {
+        if let Err(e) = with_deadline(deadline, read_proxy_header(&mut sock)).await{
+            log::warn!("Invalid PROXY protocol header from {}: {}", address, e);
+            return;
+        }
+    }
+    match context.tls_acceptor {
+        None => respond_service_unavailable(sock, retry_after).await,
+        Some(acceptor) => match with_deadline(deadline, acceptor.accept(sock)).await{
+            Ok(stream) => respond_service_unavailable(stream, retry_after).await,
+            Err(e) => log::warn!("TLS handshake with {} failed: {}", address, e),
+        },
+    }
+}
+
+async fn respond_service_unavailable<S>(mut sock: S, retry_after: u64)
+where S: AsyncRead + AsyncWrite + Unpin{
+    let write_timeout = CONFIGS.global.get_write_timeout();
+    let mut response = Vec::new();
+    respond_with_service_unavailable(&mut response, retry_after);
+    if with_timeout(write_timeout, sock.write_all(&response)).await.is_err()
+        || with_timeout(write_timeout, sock.shutdown()).await.is_err(){
+        return;
+    }
+    // the request left unread would make the close reset the connection, and the response lost
+    let mut buf = [0; 4096];
+    let _ = tokio::time::timeout(Duration::from_secs(1), async {
+        while let Ok(n) = sock.read(&mut buf).await {
+            if n == 0 {
+                break;
+            }
+        }
+    }).await;
+}
+
+async fn serve_connection<S>(mut sock: S, mut address: PeerAddress, context: ConnectionContext, permit: OwnedSemaphorePermit)
+where S: AsyncRead + AsyncWrite + Unpin + Send + 'static{
+    let ConnectionContext { listener, tls_acceptor, proxy_protocol, shutdown } = context;
+    // a single deadline covers everything up to the header of the first request
+    let header_deadline = CONFIGS.global.get_header_timeout().map(|t| Instant::now() + t);
+    // the PROXY protocol header comes before anything else, including the tls handshake
+    if proxy_protocol {
+        match with_deadline(header_deadline, read_proxy_header(&mut sock)).await{
+            Ok(Some(client)) => {
+                log::debug!("Connection from {} proxied by {}", client, address);
+                address = PeerAddress::Tcp(client);
+            },
+            // a connection opened by the load balancer itself keeps its own address
+            Ok(None) => (),
+            Err(e) => {
+                log::warn!("Invalid PROXY protocol header from {}: {}", address, e);
+                return;
+            }
+        }
+    }
+    let result = match tls_acceptor {
+        None => handle_connection(sock, Peer { address, client_cert: None, listener }, shutdown, header_deadline).await,
+        Some(acceptor) => match with_deadline(header_deadline, acceptor.accept(sock)).await{
+            Ok(stream) => {
+                let client_cert = ClientCert::from_connection(stream.get_ref().1);
+                handle_connection(stream, Peer { address, client_cert, listener }, shutdown, header_deadline).await
+            },
+            Err(e) => {
+                log::warn!("TLS handshake with {} failed: {}", address, e);
+                return;
+            }
+        },
+    };
+    if let Err(e) = result{
+        log::error!("Failed to handle connection: {}", e);
+    }
+    drop(permit);
+}
+
+#[tokio::test]
+async fn test_serve_unix_socket(){
+    use tokio::{io::{AsyncReadExt, AsyncWriteExt}, net::UnixStream};
+    use crate::shutdown::shutdown_channel;
+
+    let config_file = format!("{}/src/tests/config/hooks.test.yaml", env!("CARGO_MANIFEST_DIR"));
+    std::env::set_var("CONFIG_PATH", config_file);
+    let path = std::env::temp_dir().join(format!("rusthook-test-{}.sock", std::process::id()));
+    let config = ListenerConfig::new("local", &format!("unix:{}", path.to_str().unwrap()));
+    let listener = BoundListener::bind(&config, None).unwrap();
+    let (trigger, shutdown) = shutdown_channel();
+    let server = tokio::spawn(serve(vec![listener], ConnectionLimits { max_connections: 4, max_queued_connections: 4, retry_after: 5 }, shutdown));
+
+    let mut stream = UnixStream::connect(&path).await.unwrap();
+    stream.write_all(b"GET /webhook-test-3 HTTP/1.0\r\n\r\n").await.unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await.unwrap();
+    assert!(response.ends_with("Undefined Hook Id"));
+
+    // an idle keep-alive connection is closed on shutdown, and the socket file removed
+    let mut idle = UnixStream::connect(&path).await.unwrap();
+    idle.write_all(b"GET /webhook-test-3 HTTP/1.1\r\n\r\n").await.unwrap();
+    let mut buf = [0; 1024];
+    assert!(idle.read(&mut buf).await.unwrap() > 0);
+    trigger.trigger();
+    server.await.unwrap();
+    assert_eq!(0, idle.read(&mut buf).await.unwrap());
+    assert!(!path.exists());
+}
+
+#[tokio::test]
+async fn test_serve_busy(){
+    use tokio::{io::{AsyncReadExt, AsyncWriteExt}, net::UnixStream};
+    use crate::shutdown::shutdown_channel;
+
+    let config_file = format!("{}/src/tests/config/hooks.test.yaml", env!("CARGO_MANIFEST_DIR"));
+    std::env::set_var("CONFIG_PATH", config_file);
+    let path = std::env::temp_dir().join(format!("rusthook-test-busy-{}.sock", std::process::id()));
+    let config = ListenerConfig::new("local", &format!("unix:{}", path.to_str().unwrap()));
+    let listener = BoundListener::bind(&config, None).unwrap();
+    let (trigger, shutdown) = shutdown_channel();
+    let limits = ConnectionLimits { max_connections: 1, max_queued_connections: 1, retry_after: 7 };
+    let server = tokio::spawn(serve(vec![listener], limits, shutdown));
+
+    // one connection is served, one is queued, and the next one is turned away
+    let _served = UnixStream::connect(&path).await.unwrap();
+    let mut queued = UnixStream::connect(&path).await.unwrap();
+    let mut rejected = UnixStream::connect(&path).await.unwrap();
+    rejected.write_all(b"GET /webhook-test-1 HTTP/1.1\r\n\r\n").await.unwrap();
+    let mut response = String::new();
+    rejected.read_to_string(&mut response).await.unwrap();
+    assert!(response.starts_with("HTTP/1.1 503 Service Unavailable\r\n"));
+    assert!(response.contains("\r\nRetry-After: 7\r\n"));
+
+    // the queued connection is dropped on shutdown without being served
+    trigger.trigger();
+    server.await.unwrap();
+    let mut buf = [0; 64];
+    assert_eq!(0, queued.read(&mut buf).await.unwrap());
+}
+
+#[tokio::test]
+async fn test_reject_tls_connection(){
+    use rustls::{ClientConfig, RootCertStore, ServerConfig, pki_types::{PrivateKeyDer, PrivatePkcs8KeyDer}};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio_rustls::TlsConnector;
+    use crate::shutdown::shutdown_channel;
+
+    let config_file = format!("{}/src/tests/config/hooks.test.yaml", env!("CARGO_MANIFEST_DIR"));
+    std::env::set_var("CONFIG_PATH", config_file);
+    let cert = rcgen::generate_simple_self_signed(vec!["localhost".to_string()]).unwrap();
+    let key = PrivateKeyDer::Pkcs8(PrivatePkcs8KeyDer::from(cert.key_pair.serialize_der()));
+    let server_config = ServerConfig::builder().with_no_client_auth().with_single_cert(vec![cert.cert.der().clone()], key).unwrap();
+    let mut roots = RootCertStore::empty();
+    roots.add(cert.cert.der().clone()).unwrap();
+    let client_config = ClientConfig::builder().with_root_certificates(roots).with_no_client_auth();
+
+    let (_trigger, shutdown) = shutdown_channel();
+    let context = ConnectionContext {
+        listener: "tls".to_string(),
+        tls_acceptor: Some(TlsAcceptor::from(Arc::new(server_config))),
+        proxy_protocol: false,
+        shutdown,
+    };
+    let (client, server) = tokio::io::duplex(16384);
+    let rejection = tokio::spawn(reject_connection(server, crate::request::test_peer().address, context, 7));
+
+    // the client is told to retry later over tls rather than seeing the connection dropped
+    let connector = TlsConnector::from(Arc::new(client_config));
+    let mut stream = connector.connect("localhost".try_into().unwrap(), client).await.unwrap();
+    stream.write_all(b"GET /webhook-test-1 HTTP/1.1\r\n\r\n").await.unwrap();
+    let mut response = Vec::new();
+    let _ = stream.read_to_end(&mut response).await;
+    let response = String::from_utf8(response).unwrap();
+    assert!(response.starts_with("HTTP/1.1 503 Service Unavailable\r\n"), "{}", response);
+    assert!(response.contains("\r\nRetry-After: 7\r\n"));
+    rejection.await.unwrap();
+}
+
+#[tokio::test]
+async fn test_abort_connections(){
+    let queue = ConnectionQueue {
+        permits: Arc::new(Semaphore::new(1)),
+        limits: ConnectionLimits { max_connections: 1, max_queued_connections: 0, retry_after: 1 },
+        queued: AtomicUsize::new(0),
+        rejected: AtomicU64::new(0),
+        tasks: Mutex::new(JoinSet::new()),
+    };
+    // a connection stuck in a request holds its permit until it is aborted
+    let permit = queue.permits.clone().try_acquire_owned().unwrap();
+    queue.spawn(async move {
+        let _permit = permit;
+        std::future::pending::<()>().await;
+    });
+    queue.spawn(async {});
+    tokio::task::yield_now().await;
+    assert_eq!(0, queue.permits.available_permits());
+    queue.abort_all().await;
+    assert_eq!(1, queue.permits.available_permits());
+    assert!(queue.tasks.lock().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_header_deadline(){
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use crate::shutdown::shutdown_channel;
+
+    let config_file = format!("{}/src/tests/config/hooks.test.yaml", env!("CARGO_MANIFEST_DIR"));
+    std::env::set_var("CONFIG_PATH", config_file);
+    let (mut client, server) = tokio::io::duplex(1024);
+    let (_trigger, shutdown) = shutdown_channel();
+    let start = Instant::now();
+    let deadline = start + Duration::from_millis(300);
+    let connection = tokio::spawn(handle_connection(server, crate::request::test_peer(), shutdown, Some(deadline)));
+
+    // a request started late gets no more than what is left of the deadline
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    client.write_all(b"GET /webhook-test-1 HTTP/1.1\r\n").await.unwrap();
+    let mut response = String::new();
+    client.read_to_string(&mut response).await.unwrap();
+    assert!(response.starts_with("HTTP/1.1 408 Request Timeout\r\n"));
+    assert!(start.elapsed() < Duration::from_millis(500));
+    connection.await.unwrap().unwrap();
+}
+
+#[tokio::test]
+async fn test_body_framing(){
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use crate::shutdown::shutdown_channel;
+
+    let config_file = format!("{}/src/tests/config/hooks.test.yaml", env!("CARGO_MANIFEST_DIR"));
+    std::env::set_var("CONFIG_PATH", config_file);
+    let requests = [
+        // a body whose length cannot be told is not taken for the next request
+        ("POST /webhook-test-1 HTTP/1.1\r\nTransfer-Encoding: gzip\r\n\r\nGET /nothere HTTP/1.1\r\n\r\n", "HTTP/1.1 400 Bad Request\r\n"),
+        ("POST /webhook-test-1 HTTP/1.1\r\nTransfer-Encoding: chunked, gzip\r\n\r\n0\r\n\r\nGET /nothere HTTP/1.1\r\n\r\n", "HTTP/1.1 400 Bad Request\r\n"),
+        // nor is anything after a body framed both ways
+        ("POST /webhook-test-1 HTTP/1.1\r\nTransfer-Encoding: chunked\r\nContent-Length: 3\r\n\r\n0\r\n\r\nGET /nothere HTTP/1.1\r\n\r\n", "HTTP/1.1 "),
+        // a trailer cannot change how the body is decoded
+        ("POST /webhook-test-1 HTTP/1.1\r\nContent-Type: application/json\r\nTransfer-Encoding: chunked\r\nConnection: close\r\n\r\n2\r\n{}\r\n0\r\nContent-Encoding: br\r\n\r\n", "HTTP/1.1 200 OK\r\n"),
+    ];
+    for (request, status) in requests {
+        let (mut client, server) = tokio::io::duplex(4096);
+        let (_trigger, shutdown) = shutdown_channel();
+        let connection = tokio::spawn(handle_connection(server, crate::request::test_peer(), shutdown, None));
+        client.write_all(request.as_bytes()).await.unwrap();
+        let mut response = String::new();
+        client.read_to_string(&mut response).await.unwrap();
+        assert!(response.starts_with(status), "{}", response);
+        assert_eq!(1, response.matches("HTTP/1.1 ").count(), "{}", response);
+        assert!(response.contains("\r\nConnection: close\r\n"), "{}", response);
+        connection.await.unwrap().unwrap();
+    }
+}
+
+#[tokio::test]
+async fn test_listener_from_fd(){
+    let tcp = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    assert!(matches!(Listener::from_fd(OwnedFd::from(tcp)), Ok(Listener::Tcp(_))));
+
+    let path = std::env::temp_dir().join(format!("rusthook-test-fd-{}.sock", std::process::id()));
+    let _ = fs::remove_file(&path);
+    let unix = std::os::unix::net::UnixListener::bind(&path).unwrap();
+    match Listener::from_fd(OwnedFd::from(unix)) {
+        Ok(Listener::Unix(_, p)) => assert_eq!(path.to_str().unwrap(), p),
+        _ => panic!("expecting a unix listener"),
+    }
+    let _ = fs::remove_file(&path);
+}
@@ -0,0 +1,59 @@
+use tokio::sync::watch;
+
+/// Notify the server to stop accepting connections and drain the running ones
+pub struct ShutdownTrigger {
+    sender: watch::Sender<bool>,
+}
+
+/// Observe whether the server is shutting down
+#[derive(Clone)]
+pub struct Shutdown {
+    receiver: watch::Receiver<bool>,
+}
+
+pub fn shutdown_channel() -> (ShutdownTrigger, Shutdown){
+    let (sender, receiver) = watch::channel(false);
+    (ShutdownTrigger { sender }, Shutdown { receiver })
+}
+
+impl ShutdownTrigger {
+    pub fn trigger(&self){
+        self.sender.send_replace(true);
+    }
+}
+
+impl Shutdown {
+    pub fn is_triggered(&self) -> bool{
+        *self.receiver.borrow()
+    }
+
+    /// Resolve once the shutdown is triggered, never resolve if the trigger is dropped without it
+    pub async fn triggered(&mut self){
+        if self.receiver.wait_for(|triggered| *triggered).await.is_err(){
+            std::future::pending::<()>().await;
+        }
+    }
+}
+
+/// Wait until SIGTERM or SIGINT is received
+pub async fn wait_for_signal(){
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut sigterm = signal(SignalKind::terminate()).expect("Cannot listen to SIGTERM");
+    let mut sigint = signal(SignalKind::interrupt()).expect("Cannot listen to SIGINT");
+    tokio::select! {
+        _ = sigterm.recv() => log::info!("SIGTERM received, shutting down"),
+        _ = sigint.recv() => log::info!("SIGINT received, shutting down"),
+    }
+}
+
+#[tokio::test]
+async fn test_shutdown_channel(){
+    let (trigger, shutdown) = shutdown_channel();
+    let mut waiting = shutdown.clone();
+    let waiter = tokio::spawn(async move { waiting.triggered().await });
+    assert!(!shutdown.is_triggered());
+    trigger.trigger();
+    waiter.await.unwrap();
+    assert!(shutdown.is_triggered());
+}
@@ -0,0 +1,59 @@
+use std::{fmt, future::Future, io, net::SocketAddr, time::Duration};
+
+use tokio::time::{Instant, timeout, timeout_at};
+
+use crate::tls::ClientCert;
+
+/// The address from which a connection is accepted
+#[derive(Debug, PartialEq, Clone)]
+pub enum PeerAddress {
+    Tcp(SocketAddr),
+    /// path of the peer's socket, or of the listening socket if the peer's one is unnamed
+    Unix(String),
+}
+
+impl fmt::Display for PeerAddress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PeerAddress::Tcp(addr) => write!(f, "{}", addr),
+            PeerAddress::Unix(path) => write!(f, "unix:{}", path),
+        }
+    }
+}
+
+/// Everything known about the other end of a connection before reading any request
+#[derive(Debug, PartialEq, Clone)]
+pub struct Peer {
+    pub address: PeerAddress,
+    /// the certificate verified during the tls handshake, if any
+    pub client_cert: Option<ClientCert>,
+    /// name of the listener which accepted the connection
+    pub listener: String,
+}
+
+/// Run an io operation until the deadline, `None` means no limit
+pub async fn with_deadline<T, F>(deadline: Option<Instant>, f: F) -> io::Result<T>
+where F: Future<Output = io::Result<T>>{
+    match deadline {
+        None => f.await,
+        Some(d) => match timeout_at(d, f).await {
+            Ok(r) => r,
+            Err(_) => Err(io::Error::new(io::ErrorKind::TimedOut, "Operation Timed Out")),
+        },
+    }
+}
+
+/// Run an io operation within the time limit, `None` means no limit
+pub async fn with_timeout<T, F>(limit: Option<Duration>, f: F) -> io::Result<T>
+where F: Future<Output = io::Result<T>>{
+    match limit {
+        None => f.await,
+        Some(l) => match timeout(l, f).await {
+            Ok(r) => r,
+            Err(_) => Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                format!("Operation Timed Out After {:?}", l),
+            )),
+        },
+    }
+}
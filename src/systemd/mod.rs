@@ -0,0 +1,106 @@
+use std::{env, io, os::{fd::{FromRawFd, OwnedFd}, linux::net::SocketAddrExt, unix::net::{SocketAddr, UnixDatagram}}};
+
+/// The first file descriptor passed by the service manager
+const SD_LISTEN_FDS_START: i32 = 3;
+
+/// Take over the sockets passed by systemd socket activation, named by `FileDescriptorName=`.
+/// The environment variables are removed so that hook commands do not see them,
+/// and the returned descriptors are not inherited by hook commands.
+pub fn take_listen_fds() -> Vec<(String, OwnedFd)>{
+    let names = listen_fd_names(
+        env::var("LISTEN_PID").ok(),
+        env::var("LISTEN_FDS").ok(),
+        env::var("LISTEN_FDNAMES").ok(),
+        std::process::id(),
+    );
+    env::remove_var("LISTEN_PID");
+    env::remove_var("LISTEN_FDS");
+    env::remove_var("LISTEN_FDNAMES");
+
+    let mut fds = Vec::new();
+    for (i, name) in names.into_iter().enumerate(){
+        // SAFETY: the service manager passes `LISTEN_FDS` open descriptors starting from 3 to this process,
+        // and nothing else in the process owns them
+        let fd = unsafe { OwnedFd::from_raw_fd(SD_LISTEN_FDS_START + i as i32) };
+        // the passed descriptors are inheritable, a duplicate gets close-on-exec set
+        match fd.try_clone() {
+            Ok(fd) => fds.push((name, fd)),
+            Err(e) => log::error!("Cannot take over socket [{}] passed by systemd: {}", name, e),
+        }
+    }
+    fds
+}
+
+/// Names of the passed file descriptors, empty if they are not meant for this process
+fn listen_fd_names(listen_pid: Option<String>, listen_fds: Option<String>, fd_names: Option<String>, pid: u32) -> Vec<String>{
+    match listen_pid.and_then(|p| p.parse::<u32>().ok()) {
+        Some(p) if p == pid => (),
+        _ => return Vec::new(),
+    }
+    let count = match listen_fds.and_then(|n| n.parse::<usize>().ok()) {
+        Some(n) => n,
+        None => return Vec::new(),
+    };
+    let mut names: Vec<String> = fd_names
+        .map(|n| n.split(':').map(String::from).collect())
+        .unwrap_or_default();
+    // systemd names every socket `unknown` unless told otherwise
+    names.resize(count, String::from("unknown"));
+    names
+}
+
+/// Send service status updates to systemd through `NOTIFY_SOCKET`
+pub struct Notifier {
+    socket: Option<String>,
+}
+
+impl Notifier {
+    /// Take `NOTIFY_SOCKET` from the environment, so that hook commands cannot notify on behalf of rusthook
+    pub fn from_env() -> Self{
+        let socket = env::var("NOTIFY_SOCKET").ok();
+        env::remove_var("NOTIFY_SOCKET");
+        Notifier { socket }
+    }
+
+    /// Send a state such as `READY=1`, does nothing if not run by systemd
+    pub fn notify(&self, state: &str){
+        let socket = match &self.socket {
+            Some(s) => s,
+            None => return,
+        };
+        if let Err(e) = send_notification(socket, state){
+            log::warn!("Failed to notify systemd with {}: {}", state, e);
+        }
+    }
+}
+
+fn send_notification(socket: &str, state: &str) -> Result<(), io::Error>{
+    // a leading `@` stands for a socket in the abstract namespace
+    let addr = match socket.strip_prefix('@') {
+        Some(name) => SocketAddr::from_abstract_name(name)?,
+        None => SocketAddr::from_pathname(socket)?,
+    };
+    UnixDatagram::unbound()?.send_to_addr(state.as_bytes(), &addr)?;
+    Ok(())
+}
+
+#[test]
+fn test_listen_fd_names(){
+    let names = listen_fd_names(Some("42".into()), Some("2".into()), Some("public".into()), 42);
+    assert_eq!(vec!["public", "unknown"], names);
+    assert!(listen_fd_names(Some("41".into()), Some("2".into()), None, 42).is_empty());
+    assert!(listen_fd_names(None, Some("2".into()), None, 42).is_empty());
+}
+
+#[test]
+fn test_notify(){
+    let path = env::temp_dir().join(format!("rusthook-notify-{}.sock", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let receiver = UnixDatagram::bind(&path).unwrap();
+    let notifier = Notifier { socket: Some(path.to_str().unwrap().to_string()) };
+    notifier.notify("READY=1");
+    let mut buf = [0; 64];
+    let n = receiver.recv(&mut buf).unwrap();
+    assert_eq!(b"READY=1", &buf[..n]);
+    let _ = std::fs::remove_file(&path);
+}
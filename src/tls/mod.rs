@@ -0,0 +1,352 @@
+use std::{fs, io, sync::{Arc, RwLock}, time::{Duration, Instant, SystemTime}};
+
+use crypto::{digest::Digest, sha2::Sha256};
+use rustls::{
+    RootCertStore, ServerConfig, ServerConnection,
+    server::{ClientHello, ResolvesServerCert, WebPkiClientVerifier, danger::ClientCertVerifier},
+    sign::CertifiedKey,
+    pki_types::{CertificateDer, PrivateKeyDer, pem::PemObject},
+};
+use x509_parser::{certificate::X509Certificate, objects::{oid2abbrev, oid_registry}, prelude::FromDer, x509::X509Name};
+
+use crate::config::tls::TlsConfig;
+
+/// Build the rustls server config used to terminate tls on the listener
+pub fn build_server_config(tls: &TlsConfig) -> Result<Arc<ServerConfig>, io::Error>{
+    let resolver = CertResolver::new(tls)?;
+    let builder = ServerConfig::builder();
+    let config = match &tls.client_ca_file {
+        None => builder.with_no_client_auth(),
+        Some(ca_file) => builder.with_client_cert_verifier(build_client_verifier(ca_file, tls.client_cert_required)?),
+    }
+    .with_cert_resolver(Arc::new(resolver));
+    Ok(Arc::new(config))
+}
+
+/// Verify the client certificates against the CA bundle,
+/// clients without a certificate are accepted only if it is not required
+fn build_client_verifier(ca_file: &str, required: bool) -> Result<Arc<dyn ClientCertVerifier>, io::Error>{
+    let mut roots = RootCertStore::empty();
+    for cert in load_certs(ca_file)?{
+        if let Err(e) = roots.add(cert){
+            let err_msg = format!("Invalid CA certificate in {}: {}", ca_file, e);
+            log::error!("{}", err_msg);
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, err_msg));
+        }
+    }
+
+    let mut builder = WebPkiClientVerifier::builder(Arc::new(roots));
+    if !required{
+        builder = builder.allow_unauthenticated();
+    }
+    builder.build().map_err(|e| {
+        let err_msg = format!("Failed to build client certificate verifier from {}: {}", ca_file, e);
+        log::error!("{}", err_msg);
+        io::Error::new(io::ErrorKind::InvalidInput, err_msg)
+    })
+}
+
+/// The verified certificate presented by the client
+#[derive(Debug, PartialEq, Clone)]
+pub struct ClientCert {
+    /// distinguished name of the subject, e.g. `CN=sender, O=Example`, for display only,
+    /// since the commas inside attribute values are not escaped
+    pub subject: String,
+    /// the attributes of the subject in order, e.g. `("CN", "sender")`,
+    /// those of a multi-valued RDN one after another
+    pub subject_attributes: Vec<(String, String)>,
+    /// lowercase hex encoded sha256 digest of the DER certificate
+    pub fingerprint: String,
+}
+
+impl ClientCert {
+    /// The first certificate presented by the client of an established tls connection
+    pub fn from_connection(conn: &ServerConnection) -> Option<Self>{
+        conn.peer_certificates()
+            .and_then(|certs| certs.first())
+            .and_then(|cert| ClientCert::from_der(cert))
+    }
+
+    pub fn from_der(der: &[u8]) -> Option<Self>{
+        let (subject, subject_attributes) = match X509Certificate::from_der(der){
+            Ok((_, cert)) => (cert.subject().to_string(), subject_attributes(cert.subject())),
+            Err(e) => {
+                log::warn!("Failed to parse client certificate: {}", e);
+                return None;
+            }
+        };
+        let mut hasher = Sha256::new();
+        hasher.input(der);
+        Some(ClientCert { subject, subject_attributes, fingerprint: hasher.result_str() })
+    }
+}
+
+/// The (type, value) pairs of a name, a value that is not a string is hex encoded after `#`
+fn subject_attributes(name: &X509Name) -> Vec<(String, String)>{
+    name.iter_attributes()
+        .map(|attr| {
+            let attr_type = match oid2abbrev(attr.attr_type(), oid_registry()) {
+                Ok(abbrev) => abbrev.to_string(),
+                Err(_) => attr.attr_type().to_id_string(),
+            };
+            let value = match attr.as_str() {
+                Ok(s) => s.to_string(),
+                Err(_) => format!("#{}", attr.attr_value().data.iter().map(|b| format!("{:02x}", b)).collect::<String>()),
+            };
+            (attr_type, value)
+        })
+        .collect()
+}
+
+/// Serve the certificate from the configured files,
+/// and reload it when the files are changed if a reload interval is configured
+#[derive(Debug)]
+pub struct CertResolver {
+    cert_file: String,
+    key_file: String,
+    reload_interval: Option<Duration>,
+    state: RwLock<CertState>,
+}
+
+#[derive(Debug)]
+struct CertState {
+    key: Arc<CertifiedKey>,
+    modified: Option<SystemTime>,
+    checked_at: Instant,
+}
+
+impl CertResolver {
+    pub fn new(tls: &TlsConfig) -> Result<Self, io::Error>{
+        let key = load_certified_key(&tls.cert_file, &tls.key_file)?;
+        let modified = get_modified_time(&tls.cert_file, &tls.key_file);
+        log::info!("TLS certificate loaded from {}", tls.cert_file);
+        Ok(CertResolver {
+            cert_file: tls.cert_file.clone(),
+            key_file: tls.key_file.clone(),
+            reload_interval: tls.get_reload_interval(),
+            state: RwLock::new(CertState { key, modified, checked_at: Instant::now() }),
+        })
+    }
+
+    fn reload_if_changed(&self){
+        let interval = match self.reload_interval {
+            Some(i) => i,
+            None => return,
+        };
+        if self.state.read().unwrap().checked_at.elapsed() < interval{
+            return;
+        }
+
+        let mut state = self.state.write().unwrap();
+        state.checked_at = Instant::now();
+        let modified = get_modified_time(&self.cert_file, &self.key_file);
+        if modified == state.modified{
+            return;
+        }
+        match load_certified_key(&self.cert_file, &self.key_file){
+            Ok(key) => {
+                log::info!("TLS certificate reloaded from {}", self.cert_file);
+                state.key = key;
+                state.modified = modified;
+            },
+            // keep serving the previous certificate, the files may be partially written
+            Err(e) => log::error!("Failed to reload TLS certificate, keep using the previous one: {}", e),
+        }
+    }
+}
+
+impl ResolvesServerCert for CertResolver {
+    fn resolve(&self, _client_hello: ClientHello<'_>) -> Option<Arc<CertifiedKey>> {
+        self.reload_if_changed();
+        Some(self.state.read().unwrap().key.clone())
+    }
+}
+
+fn get_modified_time(cert_file: &str, key_file: &str) -> Option<SystemTime>{
+    [cert_file, key_file]
+        .iter()
+        .filter_map(|f| fs::metadata(f).and_then(|m| m.modified()).ok())
+        .max()
+}
+
+fn load_certs(cert_file: &str) -> Result<Vec<CertificateDer<'static>>, io::Error>{
+    let certs = CertificateDer::pem_file_iter(cert_file)
+        .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+        .map_err(|e| {
+            let err_msg = format!("Failed to read certificates from {}: {}", cert_file, e);
+            log::error!("{}", err_msg);
+            io::Error::new(io::ErrorKind::InvalidInput, err_msg)
+        })?;
+    if certs.is_empty(){
+        let err_msg = format!("No certificate found in {}", cert_file);
+        log::error!("{}", err_msg);
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, err_msg));
+    }
+    Ok(certs)
+}
+
+fn load_certified_key(cert_file: &str, key_file: &str) -> Result<Arc<CertifiedKey>, io::Error>{
+    let certs = load_certs(cert_file)?;
+
+    let key = PrivateKeyDer::from_pem_file(key_file).map_err(|e| {
+        let err_msg = format!("Failed to read private key from {}: {}", key_file, e);
+        log::error!("{}", err_msg);
+        io::Error::new(io::ErrorKind::InvalidInput, err_msg)
+    })?;
+    let signing_key = rustls::crypto::ring::sign::any_supported_type(&key).map_err(|e| {
+        let err_msg = format!("Unsupported private key in {}: {}", key_file, e);
+        log::error!("{}", err_msg);
+        io::Error::new(io::ErrorKind::InvalidInput, err_msg)
+    })?;
+    Ok(Arc::new(CertifiedKey::new(certs, signing_key)))
+}
+
+#[cfg(test)]
+fn write_self_signed_cert(dir: &std::path::Path) -> TlsConfig{
+    let cert = rcgen::generate_simple_self_signed(vec!["localhost".to_string()]).unwrap();
+    let cert_file = dir.join("cert.pem");
+    let key_file = dir.join("key.pem");
+    fs::write(&cert_file, cert.cert.pem()).unwrap();
+    fs::write(&key_file, cert.key_pair.serialize_pem()).unwrap();
+    TlsConfig::new(cert_file.to_str().unwrap(), key_file.to_str().unwrap())
+}
+
+#[cfg(test)]
+fn request_over_tls(addr: std::net::SocketAddr, ca_file: &str) -> io::Result<String>{
+    request_over_mtls(addr, ca_file, None)
+}
+
+#[cfg(test)]
+fn request_over_mtls(addr: std::net::SocketAddr, ca_file: &str, client_cert: Option<&rcgen::CertifiedKey>) -> io::Result<String>{
+    use std::{io::{Read, Write}, net::TcpStream};
+    use rustls::{ClientConfig, ClientConnection, StreamOwned};
+
+    let mut roots = RootCertStore::empty();
+    for cert in CertificateDer::pem_file_iter(ca_file).unwrap(){
+        roots.add(cert.unwrap()).unwrap();
+    }
+    let builder = ClientConfig::builder().with_root_certificates(roots);
+    let config = match client_cert {
+        None => builder.with_no_client_auth(),
+        Some(c) => builder.with_client_auth_cert(
+            vec![c.cert.der().clone()],
+            PrivateKeyDer::from_pem_slice(c.key_pair.serialize_pem().as_bytes()).unwrap(),
+        ).unwrap(),
+    };
+    let conn = ClientConnection::new(Arc::new(config), "localhost".try_into().unwrap()).unwrap();
+    let mut stream = StreamOwned::new(conn, TcpStream::connect(addr)?);
+    stream.write_all(b"ping\n")?;
+    let mut response = String::new();
+    stream.read_to_string(&mut response)?;
+    Ok(response)
+}
+
+#[cfg(test)]
+fn spawn_tls_server(server_config: Arc<ServerConfig>, connections: usize, respond: fn(&ServerConnection, &str) -> String) -> (std::net::SocketAddr, std::thread::JoinHandle<()>){
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    listener.set_nonblocking(true).unwrap();
+    let addr = listener.local_addr().unwrap();
+    let server = std::thread::spawn(move ||{
+        let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+        runtime.block_on(async move {
+            let listener = tokio::net::TcpListener::from_std(listener).unwrap();
+            let acceptor = tokio_rustls::TlsAcceptor::from(server_config);
+            for _ in 0..connections {
+                let (sock, _) = listener.accept().await.unwrap();
+                let mut stream = match acceptor.accept(sock).await{
+                    Ok(s) => s,
+                    Err(_) => continue,
+                };
+                let mut line = String::new();
+                if BufReader::new(&mut stream).read_line(&mut line).await.is_err(){
+                    continue;
+                }
+                let response = respond(stream.get_ref().1, line.trim());
+                stream.write_all(response.as_bytes()).await.unwrap();
+                let _ = stream.shutdown().await;
+            }
+        });
+    });
+    (addr, server)
+}
+
+#[test]
+fn test_tls_with_self_signed_cert(){
+    let dir = std::env::temp_dir().join(format!("rusthook-tls-test-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let mut tls = write_self_signed_cert(&dir);
+    tls.reload_interval = 1;
+    let server_config = build_server_config(&tls).unwrap();
+    let (addr, server) = spawn_tls_server(server_config, 2, |_, line| format!("pong {}", line));
+
+    let ca_file = dir.join("ca.pem");
+    fs::copy(&tls.cert_file, &ca_file).unwrap();
+    assert_eq!("pong ping", request_over_tls(addr, ca_file.to_str().unwrap()).unwrap());
+
+    // replace the certificate, the new one should be served without restarting
+    std::thread::sleep(Duration::from_millis(1100));
+    write_self_signed_cert(&dir);
+    fs::copy(&tls.cert_file, &ca_file).unwrap();
+    assert_eq!("pong ping", request_over_tls(addr, ca_file.to_str().unwrap()).unwrap());
+
+    server.join().unwrap();
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn test_mtls_with_client_cert(){
+    use rcgen::{BasicConstraints, CertificateParams, DnType, IsCa, KeyPair};
+
+    let dir = std::env::temp_dir().join(format!("rusthook-mtls-test-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let mut tls = write_self_signed_cert(&dir);
+
+    // a private CA issuing the client certificate
+    let mut ca_params = CertificateParams::new(Vec::new()).unwrap();
+    ca_params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+    ca_params.distinguished_name.push(DnType::CommonName, "rusthook test ca");
+    let ca_key = KeyPair::generate().unwrap();
+    let ca_cert = ca_params.self_signed(&ca_key).unwrap();
+    let client_ca_file = dir.join("client-ca.pem");
+    fs::write(&client_ca_file, ca_cert.pem()).unwrap();
+
+    let mut client_params = CertificateParams::new(Vec::new()).unwrap();
+    client_params.distinguished_name.push(DnType::CommonName, "alert-sender");
+    let client_key = KeyPair::generate().unwrap();
+    let client_cert = client_params.signed_by(&client_key, &ca_cert, &ca_key).unwrap();
+    let expected = ClientCert::from_der(client_cert.der()).unwrap();
+    assert_eq!("CN=alert-sender", expected.subject);
+    assert_eq!(vec![("CN".to_string(), "alert-sender".to_string())], expected.subject_attributes);
+    assert_eq!(64, expected.fingerprint.len());
+    let client = rcgen::CertifiedKey { cert: client_cert, key_pair: client_key };
+
+    tls.client_ca_file = Some(client_ca_file.to_str().unwrap().to_string());
+    let server_config = build_server_config(&tls).unwrap();
+    let (addr, server) = spawn_tls_server(server_config, 2, |conn, _| {
+        ClientCert::from_connection(conn).map(|c| c.subject).unwrap_or_default()
+    });
+
+    let ca_file = tls.cert_file.clone();
+    assert_eq!("CN=alert-sender", request_over_mtls(addr, &ca_file, Some(&client)).unwrap());
+    // the client certificate is required
+    assert!(request_over_tls(addr, &ca_file).is_err());
+
+    server.join().unwrap();
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn test_client_cert_subject_attributes(){
+    use rcgen::{CertificateParams, DistinguishedName, DnType, KeyPair};
+
+    // a comma inside a value is not a separator, though the display string cannot tell
+    let mut params = CertificateParams::new(Vec::new()).unwrap();
+    params.distinguished_name = DistinguishedName::new();
+    params.distinguished_name.push(DnType::OrganizationName, "x, CN=alert-sender");
+    let cert = params.self_signed(&KeyPair::generate().unwrap()).unwrap();
+    let client_cert = ClientCert::from_der(cert.der()).unwrap();
+    assert_eq!("O=x, CN=alert-sender", client_cert.subject);
+    assert_eq!(vec![("O".to_string(), "x, CN=alert-sender".to_string())], client_cert.subject_attributes);
+}